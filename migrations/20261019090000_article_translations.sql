@@ -0,0 +1,13 @@
+-- Language of the original text stored in the articles row
+ALTER TABLE articles ADD COLUMN language VARCHAR(16) NOT NULL DEFAULT 'el';
+
+-- Translations of an article, the markdown lives next to the original as {id}.{language}.md
+CREATE TABLE article_translations (
+    id SERIAL PRIMARY KEY,
+    article_id INTEGER NOT NULL REFERENCES articles(id) ON DELETE CASCADE,
+    language VARCHAR(16) NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT NOT NULL,
+    md_filename TEXT NOT NULL,
+    UNIQUE (article_id, language)
+);
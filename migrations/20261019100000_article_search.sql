@@ -0,0 +1,14 @@
+-- Plain text of the markdown body, kept up to date by create_article and update_article
+ALTER TABLE articles ADD COLUMN search_body TEXT NOT NULL DEFAULT '';
+
+-- Greek and English stemming of title (A), description (B) and body (C)
+ALTER TABLE articles ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', title), 'A') ||
+    setweight(to_tsvector('greek', title), 'A') ||
+    setweight(to_tsvector('english', description), 'B') ||
+    setweight(to_tsvector('greek', description), 'B') ||
+    setweight(to_tsvector('english', search_body), 'C') ||
+    setweight(to_tsvector('greek', search_body), 'C')
+) STORED;
+
+CREATE INDEX articles_search_vector_idx ON articles USING GIN (search_vector);
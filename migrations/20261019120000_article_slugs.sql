@@ -0,0 +1,11 @@
+-- Readable article address, existing rows get theirs from the title on the next startup
+ALTER TABLE articles ADD COLUMN slug VARCHAR(200) UNIQUE;
+
+-- Slugs an article was reachable under before, they redirect to the current one
+CREATE TABLE article_slug_history (
+    slug VARCHAR(200) PRIMARY KEY,
+    article_id INTEGER NOT NULL REFERENCES articles(id) ON DELETE CASCADE,
+    replaced_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX article_slug_history_article_id_idx ON article_slug_history (article_id);
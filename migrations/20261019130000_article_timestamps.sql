@@ -0,0 +1,10 @@
+-- Existing articles get the time of the migration, the closest thing to a creation date they have
+ALTER TABLE articles
+    ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ADD COLUMN published_at TIMESTAMPTZ,
+    ADD COLUMN author_id INTEGER REFERENCES users(id) ON DELETE SET NULL;
+
+UPDATE articles SET published_at = created_at;
+
+CREATE INDEX articles_updated_at_idx ON articles (updated_at, id);
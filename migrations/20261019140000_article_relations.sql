@@ -0,0 +1,10 @@
+-- Precomputed related articles, one row per direction of every pair worth recommending
+CREATE TABLE article_relations (
+    article_id INTEGER NOT NULL REFERENCES articles(id) ON DELETE CASCADE,
+    related_id INTEGER NOT NULL REFERENCES articles(id) ON DELETE CASCADE,
+    score REAL NOT NULL,
+    PRIMARY KEY (article_id, related_id)
+);
+
+CREATE INDEX article_relations_related_id_idx ON article_relations (related_id);
+CREATE INDEX article_relations_score_idx ON article_relations (article_id, score DESC);
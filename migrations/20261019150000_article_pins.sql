@@ -0,0 +1,6 @@
+-- Pinned articles lead the listings by position (1 first) until pinned_until, if set
+ALTER TABLE articles
+    ADD COLUMN pinned_position INTEGER CHECK (pinned_position > 0),
+    ADD COLUMN pinned_until TIMESTAMPTZ;
+
+CREATE INDEX articles_pinned_idx ON articles (pinned_position, id) WHERE pinned_position IS NOT NULL;
@@ -0,0 +1,16 @@
+-- Multi-part write-ups, an article belongs to at most one series
+CREATE TABLE series (
+    id SERIAL PRIMARY KEY,
+    slug VARCHAR(200) NOT NULL UNIQUE,
+    title TEXT NOT NULL,
+    description TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE series_articles (
+    series_id INTEGER NOT NULL REFERENCES series(id) ON DELETE CASCADE,
+    article_id INTEGER NOT NULL UNIQUE REFERENCES articles(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL, -- reading order, gaps left by deleted articles are fine
+    PRIMARY KEY (series_id, article_id)
+);
+
+CREATE INDEX series_articles_order_idx ON series_articles (series_id, position);
@@ -0,0 +1,9 @@
+-- 0 draft, 1 scheduled, 2 published, 3 archived, see ArticleStatus. Existing articles were all public.
+ALTER TABLE articles
+    ADD COLUMN status INTEGER NOT NULL DEFAULT 2 CHECK (status BETWEEN 0 AND 3),
+    ADD COLUMN publish_at TIMESTAMPTZ;
+
+ALTER TABLE articles ALTER COLUMN status DROP DEFAULT;
+
+-- What the background publisher looks for
+CREATE INDEX articles_scheduled_idx ON articles (publish_at) WHERE status = 1;
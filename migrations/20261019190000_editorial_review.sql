@@ -0,0 +1,20 @@
+-- 0 contributor, 1 editor, see UserRole. Everyone who could sign in so far could publish.
+ALTER TABLE users ADD COLUMN role INTEGER NOT NULL DEFAULT 1 CHECK (role BETWEEN 0 AND 1);
+
+ALTER TABLE users ALTER COLUMN role SET DEFAULT 0;
+
+-- 4 in review, 5 changes requested
+ALTER TABLE articles DROP CONSTRAINT articles_status_check;
+ALTER TABLE articles ADD CONSTRAINT articles_status_check CHECK (status BETWEEN 0 AND 5);
+
+-- Comments and transitions of the review of an article, see ReviewAction
+CREATE TABLE article_reviews (
+    id SERIAL PRIMARY KEY,
+    article_id INTEGER NOT NULL REFERENCES articles(id) ON DELETE CASCADE,
+    user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    action INTEGER NOT NULL CHECK (action BETWEEN 0 AND 3),
+    comment TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX article_reviews_article_idx ON article_reviews (article_id, created_at);
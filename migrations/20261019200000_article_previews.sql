@@ -0,0 +1,13 @@
+-- Links that show an unpublished article to someone without an account.
+-- The token is signed from id, article_id and expires_at, so it is not stored.
+CREATE TABLE article_previews (
+    id SERIAL PRIMARY KEY,
+    article_id INTEGER NOT NULL REFERENCES articles(id) ON DELETE CASCADE,
+    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    label TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX article_previews_article_idx ON article_previews (article_id);
@@ -0,0 +1,11 @@
+-- 6 trashed, see ArticleStatus. A trashed article keeps the status it goes back to when restored.
+ALTER TABLE articles DROP CONSTRAINT articles_status_check;
+ALTER TABLE articles ADD CONSTRAINT articles_status_check CHECK (status BETWEEN 0 AND 6);
+
+ALTER TABLE articles
+    ADD COLUMN deleted_at TIMESTAMPTZ,
+    ADD COLUMN deleted_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    ADD COLUMN status_before_delete INTEGER;
+
+-- What the purge looks for
+CREATE INDEX articles_trash_idx ON articles (deleted_at) WHERE status = 6;
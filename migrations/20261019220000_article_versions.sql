@@ -0,0 +1,2 @@
+-- Raised by every write to an article, its ETag. Updates and deletes send it back in If-Match.
+ALTER TABLE articles ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,153 @@
+use std::env;
+use actix_web::web::{Data, Path, Query};
+use actix_web::{HttpRequest, HttpResponse};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use crate::entities::{ArchiveBucket, ArchiveMonth, ArchiveYear};
+use crate::enums::{ArticleStatus, SortField};
+use crate::i18n::{requested_languages, LanguageQuery};
+use crate::listing::{ArticleListing, ListQuery};
+use crate::pagination::PageQuery;
+use crate::services::respond_with_listing;
+use crate::utils::log_with_colors;
+use crate::AppState;
+
+// Months start and end at midnight in ARCHIVE_TIME_ZONE (an IANA name such as
+// Europe/Athens), so an article published late on the 31st lands in the month readers expect
+pub fn archive_time_zone() -> String {
+    env::var("ARCHIVE_TIME_ZONE").unwrap_or_else(|_| "UTC".to_string())
+}
+
+// A year, or a month of a year, of publication dates
+pub struct ArchivePeriod {
+    pub(crate) year: i32,
+    pub(crate) month: Option<i32>,
+}
+
+impl ArchivePeriod {
+    pub fn new(year: i32, month: Option<i32>) -> Option<Self> {
+        let year_ok = (1..=9999).contains(&year);
+        let month_ok = month.is_none_or(|month| (1..=12).contains(&month));
+        (year_ok && month_ok).then_some(ArchivePeriod { year, month })
+    }
+
+    // Appends the publication range condition, the query must already end in a WHERE clause
+    pub fn push_range(&self, builder: &mut QueryBuilder<Postgres>) {
+        let length = if self.month.is_some() { "1 month" } else { "1 year" };
+        let time_zone = archive_time_zone();
+
+        builder
+            .push(" AND published_at >= make_timestamptz(")
+            .push_bind(self.year)
+            .push(", ")
+            .push_bind(self.month.unwrap_or(1))
+            .push(", 1, 0, 0, 0, ")
+            .push_bind(time_zone.clone())
+            .push(") AND published_at < make_timestamptz(")
+            .push_bind(self.year)
+            .push(", ")
+            .push_bind(self.month.unwrap_or(1))
+            .push(", 1, 0, 0, 0, ")
+            .push_bind(time_zone)
+            .push(format!(") + INTERVAL '{}'", length));
+    }
+}
+
+// Article counts per month, newest first, grouped into years
+pub async fn load_archive(db: &PgPool) -> Result<Vec<ArchiveYear>, sqlx::Error> {
+    let buckets = sqlx::query_as::<_, ArchiveBucket>(
+        r#"
+        SELECT
+            EXTRACT(YEAR FROM published_at AT TIME ZONE $1)::INTEGER AS year,
+            EXTRACT(MONTH FROM published_at AT TIME ZONE $1)::INTEGER AS month,
+            COUNT(*) AS article_count
+        FROM articles
+        WHERE published_at IS NOT NULL AND status = $2
+        GROUP BY 1, 2
+        ORDER BY 1 DESC, 2 DESC
+        "#
+    )
+        .bind(archive_time_zone())
+        .bind::<i32>(ArticleStatus::Published.into())
+        .fetch_all(db)
+        .await?;
+
+    let mut years: Vec<ArchiveYear> = Vec::new();
+    for bucket in buckets {
+        let month = ArchiveMonth { month: bucket.month, article_count: bucket.article_count };
+        match years.last_mut() {
+            Some(year) if year.year == bucket.year => {
+                year.article_count += bucket.article_count;
+                year.months.push(month);
+            }
+            _ => years.push(ArchiveYear {
+                year: bucket.year,
+                article_count: bucket.article_count,
+                months: vec![month],
+            }),
+        }
+    }
+
+    Ok(years)
+}
+
+//#[get("/archive")]
+pub async fn fetch_archive(state: Data<AppState>) -> HttpResponse {
+    match load_archive(&state.db).await {
+        Ok(years) => {
+            log_with_colors("INFO", "GET 200 /archive");
+            HttpResponse::Ok().json(years)
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to load archive")
+        }
+    }
+}
+
+// Same paging and filters as GET /articles, newest publication first unless ?sort= says otherwise
+async fn respond_with_period(
+    req: &HttpRequest,
+    state: &AppState,
+    period: Option<ArchivePeriod>,
+    query: &LanguageQuery,
+    page_query: &PageQuery,
+    list_query: &ListQuery,
+    route: &str,
+) -> HttpResponse {
+    let Some(period) = period else {
+        log_with_colors("WARN", &format!("GET 404 {}", route));
+        return HttpResponse::NotFound().body("No such archive period");
+    };
+
+    let listing = ArticleListing::with_default_sort(list_query, SortField::Published)
+        .map(|listing| ArticleListing { published_in: Some(period), ..listing });
+    let languages = requested_languages(req, query);
+    respond_with_listing(req, &state.db, &languages, listing, page_query, route).await
+}
+
+//#[get("/archive/{year}")]
+pub async fn fetch_archive_year(
+    req: HttpRequest,
+    state: Data<AppState>,
+    year: Path<i32>,
+    query: Query<LanguageQuery>,
+    page_query: Query<PageQuery>,
+    list_query: Query<ListQuery>,
+) -> HttpResponse {
+    let period = ArchivePeriod::new(year.into_inner(), None);
+    respond_with_period(&req, &state, period, &query, &page_query, &list_query, "/archive/{year}").await
+}
+
+//#[get("/archive/{year}/{month}")]
+pub async fn fetch_archive_month(
+    req: HttpRequest,
+    state: Data<AppState>,
+    path: Path<(i32, i32)>,
+    query: Query<LanguageQuery>,
+    page_query: Query<PageQuery>,
+    list_query: Query<ListQuery>,
+) -> HttpResponse {
+    let (year, month) = path.into_inner();
+    let period = ArchivePeriod::new(year, Some(month));
+    respond_with_period(&req, &state, period, &query, &page_query, &list_query, "/archive/{year}/{month}").await
+}
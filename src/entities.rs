@@ -1,18 +1,43 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
-use crate::enums::ArticleType;
+use crate::enums::{ArticleStatus, ArticleType, DiffMode, DiffOp, LintRule};
 
 
 //ARTICLE STRUCTS
 
-#[derive(Serialize, Deserialize, FromRow)]
+#[derive(Serialize, Deserialize, FromRow, Clone)]
 pub struct ArticleEntity {
     pub(crate) id : i32,
     pub(crate) title: String,
     pub(crate) description:String,
     pub(crate) md_filename:String,
     pub(crate) photo_filename:String,
-    pub(crate) article_type:i32
+    pub(crate) article_type:i32,
+    #[serde(default)]
+    pub(crate) language:String, // language of the text currently held, the original or a translation
+    #[serde(default)]
+    pub(crate) slug:String, // empty in an update keeps the current slug
+    // Set by the server, ignored when sent in an update
+    #[serde(default)]
+    pub(crate) created_at:DateTime<Utc>,
+    #[serde(default)]
+    pub(crate) updated_at:DateTime<Utc>,
+    #[serde(default)]
+    pub(crate) published_at:Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub(crate) author_id:Option<i32>, // user that created the article
+    #[serde(default)]
+    pub(crate) pinned_position:Option<i32>, // set through the pin routes, 1 leads the listings
+    #[serde(default)]
+    pub(crate) pinned_until:Option<DateTime<Utc>>,
+    // Set through status and publish_at of the create and update requests
+    #[serde(default)]
+    pub(crate) status:i32, // see ArticleStatus
+    #[serde(default)]
+    pub(crate) publish_at:Option<DateTime<Utc>>, // when a scheduled article goes public
+    #[serde(default)]
+    pub(crate) version:i32, // raised by every write, the ETag of the article
 }
 
 
@@ -30,35 +55,39 @@ pub struct ArticleResponse {
     pub(crate) article: ArticleEntity,
     pub(crate) md_contents: String,
     pub(crate) photo_contents: String, // Base64 encoded photo
+    pub(crate) available_languages: Vec<String>,
+    pub(crate) tags: Vec<TagEntity>,
+    pub(crate) related: Vec<RelatedArticle>,
+    pub(crate) series: Option<SeriesInfo>,
 }
 
-impl From<ArticleEntity> for Article {
-    fn from(entity: ArticleEntity) -> Self {
-        Article {
+#[derive(Serialize)]
+pub struct ArticleListItem {
+    #[serde(flatten)]
+    pub(crate) article: ArticleEntity,
+    pub(crate) available_languages: Vec<String>,
+    pub(crate) tags: Vec<TagEntity>,
+}
+
+impl TryFrom<ArticleEntity> for Article {
+    type Error = String;
+
+    fn try_from(entity: ArticleEntity) -> Result<Self, Self::Error> {
+        Ok(Article {
             id: entity.id,
             title: entity.title,
             description: entity.description,
             md_filename: entity.md_filename,
             photo_filename: entity.photo_filename,
-            article_type: ArticleType::from(entity.article_type),
-        }
+            article_type: ArticleType::try_from(entity.article_type)?,
+        })
     }
 }
 
 impl ArticleEntity {
-    // Create an ArticleEntity from a successful insert and generated filenames
-    pub fn from_insert(id: i32, title: String, description: String, article_type: i32) -> Self {
-        let md_filename = format!("{}.md", id);
-        let photo_filename = format!("{}.jpg", id); // Change extension if needed
-
-        ArticleEntity {
-            id,
-            title,
-            description,
-            md_filename,
-            photo_filename,
-            article_type,
-        }
+    // Whether the public routes show the article
+    pub fn is_public(&self) -> bool {
+        ArticleStatus::from(self.status) == ArticleStatus::Published
     }
 }
 
@@ -66,18 +95,412 @@ impl ArticleEntity {
 pub struct ArticleCreateRequest{
     pub(crate) title:String,
     pub(crate) description:String,
-    pub(crate) article_type:i32
+    pub(crate) article_type:i32,
+    #[serde(default)]
+    pub(crate) language: Option<String>, // DEFAULT_LANGUAGE when missing
+    #[serde(default)]
+    pub(crate) tags: Vec<String>, // tag names, created on first use
+    #[serde(default)]
+    pub(crate) slug: Option<String>, // generated from the title when missing
+    #[serde(default)]
+    pub(crate) status: Option<i32>, // published, or scheduled when publish_at is set
+    #[serde(default)]
+    pub(crate) publish_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArticleUpdateRequest {
+    #[serde(flatten)]
+    pub(crate) article: ArticleEntity,
+    #[serde(default)]
+    pub(crate) tags: Option<Vec<String>>, // replaces the tags when present, keeps them when missing
+    #[serde(default)]
+    pub(crate) status: Option<i32>, // kept when missing, scheduled when only publish_at is set
+    #[serde(default)]
+    pub(crate) publish_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct ArticleWriteResponse {
+    pub(crate) article: ArticleEntity,
+    pub(crate) tags: Vec<TagEntity>,
+    pub(crate) warnings: Vec<LintWarning>,
+}
+
+
+// LINT STRUCTS
+
+#[derive(Serialize)]
+pub struct LintWarning {
+    pub(crate) rule: LintRule,
+    pub(crate) message: String,
+    pub(crate) line: Option<usize>, // 1-based line in the markdown, None for the title
+    pub(crate) blocking: bool,
+}
+
+
+// TRANSLATION STRUCTS
+
+#[derive(Serialize, Deserialize, FromRow, Clone)]
+pub struct ArticleTranslationEntity {
+    pub(crate) id: i32,
+    pub(crate) article_id: i32,
+    pub(crate) language: String,
+    pub(crate) title: String,
+    pub(crate) description: String,
+    pub(crate) md_filename: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TranslationRequest {
+    pub(crate) title: String,
+    pub(crate) description: String,
+}
+
+
+// TAG STRUCTS
+
+#[derive(Serialize, Deserialize, FromRow, Clone)]
+pub struct TagEntity {
+    pub(crate) id: i32,
+    pub(crate) slug: String,
+    pub(crate) name: String,
+}
+
+// A tag together with the article it is attached to, for loading the tags of many articles at once
+#[derive(FromRow)]
+pub struct ArticleTagEntity {
+    pub(crate) article_id: i32,
+    #[sqlx(flatten)]
+    pub(crate) tag: TagEntity,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct TagCount {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub(crate) tag: TagEntity,
+    pub(crate) article_count: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TagRenameRequest {
+    pub(crate) name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TagMergeRequest {
+    pub(crate) into: String, // slug of the tag that takes over the articles
+}
+
+
+// RELATED STRUCTS
+
+#[derive(Serialize, FromRow, Clone)]
+pub struct RelatedArticle {
+    pub(crate) id: i32,
+    pub(crate) slug: String,
+    pub(crate) title: String,
+    pub(crate) description: String,
+    pub(crate) article_type: i32,
+    pub(crate) score: f32,
+}
+
+// What the relation scores are computed from
+#[derive(FromRow)]
+pub struct RelationSource {
+    pub(crate) id: i32,
+    pub(crate) article_type: i32,
+    pub(crate) title: String,
+    pub(crate) description: String,
+    pub(crate) search_body: String,
+    pub(crate) tag_ids: Vec<i32>,
+}
+
+
+// SERIES STRUCTS
+
+#[derive(Serialize, Deserialize, FromRow, Clone)]
+pub struct SeriesEntity {
+    pub(crate) id: i32,
+    pub(crate) slug: String,
+    pub(crate) title: String,
+    pub(crate) description: String,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct SeriesCount {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub(crate) series: SeriesEntity,
+    pub(crate) article_count: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SeriesRequest {
+    pub(crate) title: String,
+    #[serde(default)]
+    pub(crate) description: String,
+    #[serde(default)]
+    pub(crate) slug: Option<String>, // generated from the title when creating, kept when updating
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SeriesArticlesRequest {
+    pub(crate) articles: Vec<i32>, // article ids in reading order
+}
+
+#[derive(Serialize)]
+pub struct SeriesResponse {
+    #[serde(flatten)]
+    pub(crate) series: SeriesEntity,
+    pub(crate) articles: Vec<ArticleListItem>, // in reading order
+}
+
+// A part of a series, as linked from its neighbours
+#[derive(Serialize, FromRow, Clone)]
+pub struct SeriesPart {
+    pub(crate) id: i32,
+    pub(crate) slug: String,
+    pub(crate) title: String,
+}
+
+// Where an article stands in its series
+#[derive(Serialize, Clone)]
+pub struct SeriesInfo {
+    pub(crate) id: i32,
+    pub(crate) slug: String,
+    pub(crate) title: String,
+    pub(crate) position: usize, // 1-based
+    pub(crate) total: usize,
+    pub(crate) previous: Option<SeriesPart>,
+    pub(crate) next: Option<SeriesPart>,
+}
+
+
+// REVISION STRUCTS
+
+#[derive(Serialize, FromRow)]
+pub struct ArticleRevisionEntity {
+    pub(crate) id: i32,
+    pub(crate) article_id: i32,
+    pub(crate) number: i32, // 1 for the oldest revision of the article
+    pub(crate) title: String,
+    pub(crate) description: String,
+    pub(crate) article_type: i32,
+    pub(crate) markdown: String,
+    pub(crate) editor_id: Option<i32>,
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct ArticleRevision {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub(crate) revision: ArticleRevisionEntity,
+    pub(crate) editor_email: Option<String>,
+}
+
+// A revision in the list, without its markdown
+#[derive(Serialize, FromRow)]
+pub struct RevisionSummary {
+    pub(crate) number: i32,
+    pub(crate) title: String,
+    pub(crate) editor_email: Option<String>,
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct DiffChange {
+    pub(crate) op: DiffOp,
+    pub(crate) text: String,
+}
+
+#[derive(Serialize)]
+pub struct RevisionDiff {
+    pub(crate) from: i32,
+    pub(crate) to: i32,
+    pub(crate) mode: DiffMode,
+    pub(crate) title: Vec<DiffChange>,
+    pub(crate) description: Vec<DiffChange>,
+    pub(crate) markdown: Vec<DiffChange>,
+    pub(crate) unified: String, // the markdown change as a unified diff
+}
+
+
+// REVIEW STRUCTS
+
+#[derive(Serialize, FromRow)]
+pub struct ReviewEntity {
+    pub(crate) id: i32,
+    pub(crate) article_id: i32,
+    pub(crate) user_id: Option<i32>,
+    pub(crate) action: i32, // see ReviewAction
+    pub(crate) comment: String,
+    pub(crate) created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct Review {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub(crate) review: ReviewEntity,
+    pub(crate) user_email: Option<String>,
+}
+
+// Body of the review transitions and of a comment. publish_at schedules an approved article.
+#[derive(Deserialize, Default)]
+pub struct ReviewRequest {
+    #[serde(default)]
+    pub(crate) comment: String,
+    #[serde(default)]
+    pub(crate) publish_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct ReviewQueueItem {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub(crate) article: ArticleEntity,
+    pub(crate) author_email: Option<String>,
+    pub(crate) submitted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct RoleRequest {
+    pub(crate) role: i32, // see UserRole
+}
+
+
+// PREVIEW STRUCTS
+
+#[derive(Serialize, FromRow)]
+pub struct PreviewEntity {
+    pub(crate) id: i32,
+    pub(crate) article_id: i32,
+    pub(crate) created_by: Option<i32>,
+    pub(crate) label: String, // who the link was made for
+    pub(crate) created_at: DateTime<Utc>,
+    pub(crate) expires_at: DateTime<Utc>,
+    pub(crate) revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct PreviewRequest {
+    #[serde(default)]
+    pub(crate) label: String,
+    #[serde(default)]
+    pub(crate) expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct PreviewLink {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub(crate) preview: PreviewEntity,
+    pub(crate) article_slug: String,
+    pub(crate) created_by_email: Option<String>,
+    #[sqlx(skip)]
+    pub(crate) token: String,
+    #[sqlx(skip)]
+    pub(crate) url: String,
+}
+
+
+// TRASH STRUCTS
+
+#[derive(Serialize, FromRow)]
+pub struct TrashItem {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub(crate) article: ArticleEntity,
+    pub(crate) deleted_at: DateTime<Utc>,
+    pub(crate) deleted_by_email: Option<String>,
+    pub(crate) purge_at: DateTime<Utc>, // when the article and its files are removed for good
+}
+
+
+// FEED STRUCTS
+
+#[derive(FromRow)]
+pub struct FeedArticle {
+    #[sqlx(flatten)]
+    pub(crate) article: ArticleEntity,
+    pub(crate) author_email: Option<String>,
+}
+
+
+// PIN STRUCTS
+
+#[derive(Serialize, Deserialize)]
+pub struct PinRequest {
+    pub(crate) position: i32,
+    #[serde(default)]
+    pub(crate) pinned_until: Option<DateTime<Utc>>, // pinned for good when missing
+}
+
+
+// ARCHIVE STRUCTS
+
+#[derive(FromRow)]
+pub struct ArchiveBucket {
+    pub(crate) year: i32,
+    pub(crate) month: i32,
+    pub(crate) article_count: i64,
+}
+
+#[derive(Serialize)]
+pub struct ArchiveYear {
+    pub(crate) year: i32,
+    pub(crate) article_count: i64,
+    pub(crate) months: Vec<ArchiveMonth>,
+}
+
+#[derive(Serialize)]
+pub struct ArchiveMonth {
+    pub(crate) month: i32,
+    pub(crate) article_count: i64,
+}
+
+
+// SEARCH STRUCTS
+
+#[derive(Serialize, FromRow)]
+pub struct SearchResult {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub(crate) article: ArticleEntity,
+    pub(crate) rank: f32,
+    pub(crate) title_highlight: String, // title with the matches wrapped in <mark>
+    pub(crate) snippet: String,         // best matching fragments of the description and body
+    #[serde(skip)]
+    pub(crate) total: i64,
+}
+
+#[derive(Serialize)]
+pub struct SearchResponse {
+    pub(crate) query: String,
+    pub(crate) total: i64,
+    pub(crate) results: Vec<SearchResult>,
 }
 
 
 // CLAIM STRUCTS
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Claims {
     pub(crate) sub: String,
     pub(crate) exp: usize,
 }
 
+// Claims of a preview link. Without a subject it never passes for a login token, nor a login token for it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PreviewClaims {
+    pub(crate) preview: i32,
+    pub(crate) article: i32,
+    pub(crate) exp: usize,
+}
+
 
 
 // LOGIN-USER STRUCTS
@@ -94,12 +517,15 @@ pub struct User {
     pub(crate) id: i32,
     pub(crate) email: String,
     pub(crate) password_hash: String,
+    pub(crate) role: i32, // see UserRole
 }
 
 #[derive(Deserialize)]
 pub struct SignupRequest {
     pub(crate) email: String,
     pub(crate) password: String,
+    #[serde(default)]
+    pub(crate) role: Option<i32>, // contributor when missing
 }
 
 
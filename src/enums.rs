@@ -1,29 +1,273 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, async_graphql::Enum)]
 pub enum ArticleType{
     Important,
     Favourite,
     Common,
 }
 
-impl From<i32> for ArticleType {
-    fn from(value: i32) -> Self {
+// The column is written from requests, a value that is not a type is an error rather than a panic
+impl TryFrom<i32> for ArticleType {
+    type Error = String;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
         match value {
-            0 => ArticleType::Important,
-            1 => ArticleType::Favourite,
-            2 => ArticleType::Common,
-            _ => panic!("Invalid ArticleType value"),
+            0 => Ok(ArticleType::Important),
+            1 => Ok(ArticleType::Favourite),
+            2 => Ok(ArticleType::Common),
+            _ => Err(format!("Invalid article type {}", value)),
         }
     }
 }
 
-impl Into<i32> for ArticleType {
-    fn into(self) -> i32 {
+impl ArticleType {
+    // URL segment of the type pages, e.g. /types/important
+    pub fn slug(&self) -> &'static str {
         match self {
+            ArticleType::Important => "important",
+            ArticleType::Favourite => "favourite",
+            ArticleType::Common => "common",
+        }
+    }
+
+    pub fn from_slug(slug: &str) -> Option<Self> {
+        match slug {
+            "important" => Some(ArticleType::Important),
+            "favourite" => Some(ArticleType::Favourite),
+            "common" => Some(ArticleType::Common),
+            _ => None,
+        }
+    }
+}
+
+impl From<ArticleType> for i32 {
+    fn from(value: ArticleType) -> Self {
+        match value {
             ArticleType::Important => 0,
             ArticleType::Favourite => 1,
             ArticleType::Common => 2,
         }
     }
-}
\ No newline at end of file
+}
+
+// Where an article is in its life, only published articles are shown publicly.
+// Scheduled articles are published by the background publisher at their publish_at.
+// InReview and ChangesRequested are only reached through the review transitions,
+// Trashed by deleting the article.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, async_graphql::Enum)]
+pub enum ArticleStatus {
+    Draft,
+    Scheduled,
+    Published,
+    Archived,
+    InReview,
+    ChangesRequested,
+    Trashed,
+}
+
+impl From<i32> for ArticleStatus {
+    fn from(value: i32) -> Self {
+        match value {
+            0 => ArticleStatus::Draft,
+            1 => ArticleStatus::Scheduled,
+            2 => ArticleStatus::Published,
+            3 => ArticleStatus::Archived,
+            4 => ArticleStatus::InReview,
+            5 => ArticleStatus::ChangesRequested,
+            6 => ArticleStatus::Trashed,
+            _ => panic!("Invalid ArticleStatus value"),
+        }
+    }
+}
+
+impl From<ArticleStatus> for i32 {
+    fn from(value: ArticleStatus) -> Self {
+        match value {
+            ArticleStatus::Draft => 0,
+            ArticleStatus::Scheduled => 1,
+            ArticleStatus::Published => 2,
+            ArticleStatus::Archived => 3,
+            ArticleStatus::InReview => 4,
+            ArticleStatus::ChangesRequested => 5,
+            ArticleStatus::Trashed => 6,
+        }
+    }
+}
+
+impl ArticleStatus {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "draft" => Some(ArticleStatus::Draft),
+            "scheduled" => Some(ArticleStatus::Scheduled),
+            "published" => Some(ArticleStatus::Published),
+            "archived" => Some(ArticleStatus::Archived),
+            "in_review" => Some(ArticleStatus::InReview),
+            "changes_requested" => Some(ArticleStatus::ChangesRequested),
+            "trashed" => Some(ArticleStatus::Trashed),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ArticleStatus::Draft => "draft",
+            ArticleStatus::Scheduled => "scheduled",
+            ArticleStatus::Published => "published",
+            ArticleStatus::Archived => "archived",
+            ArticleStatus::InReview => "in_review",
+            ArticleStatus::ChangesRequested => "changes_requested",
+            ArticleStatus::Trashed => "trashed",
+        }
+    }
+}
+
+// What a user may do. Contributors write drafts and submit them for review,
+// editors review, publish and manage everything else.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, async_graphql::Enum)]
+pub enum UserRole {
+    Contributor,
+    Editor,
+}
+
+impl From<i32> for UserRole {
+    fn from(value: i32) -> Self {
+        match value {
+            0 => UserRole::Contributor,
+            1 => UserRole::Editor,
+            _ => panic!("Invalid UserRole value"),
+        }
+    }
+}
+
+impl From<UserRole> for i32 {
+    fn from(value: UserRole) -> Self {
+        match value {
+            UserRole::Contributor => 0,
+            UserRole::Editor => 1,
+        }
+    }
+}
+
+// An entry of the review history of an article
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReviewAction {
+    Comment,
+    Submit,
+    RequestChanges,
+    Approve,
+}
+
+impl From<i32> for ReviewAction {
+    fn from(value: i32) -> Self {
+        match value {
+            0 => ReviewAction::Comment,
+            1 => ReviewAction::Submit,
+            2 => ReviewAction::RequestChanges,
+            3 => ReviewAction::Approve,
+            _ => panic!("Invalid ReviewAction value"),
+        }
+    }
+}
+
+impl From<ReviewAction> for i32 {
+    fn from(value: ReviewAction) -> Self {
+        match value {
+            ReviewAction::Comment => 0,
+            ReviewAction::Submit => 1,
+            ReviewAction::RequestChanges => 2,
+            ReviewAction::Approve => 3,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug, async_graphql::Enum)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintRule {
+    MissingAltText,
+    EmptyLink,
+    HeadingJump,
+    DuplicateHeading,
+    RawHtml,
+    TitleTooLong,
+}
+
+impl LintRule {
+    // Parse the kebab-case names used in LINT_BLOCKING_RULES
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim() {
+            "missing-alt-text" => Some(LintRule::MissingAltText),
+            "empty-link" => Some(LintRule::EmptyLink),
+            "heading-jump" => Some(LintRule::HeadingJump),
+            "duplicate-heading" => Some(LintRule::DuplicateHeading),
+            "raw-html" => Some(LintRule::RawHtml),
+            "title-too-long" => Some(LintRule::TitleTooLong),
+            _ => None,
+        }
+    }
+}
+
+
+// Orderings the article listing accepts through ?sort=
+#[derive(Clone, Copy, PartialEq, Eq, Debug, async_graphql::Enum)]
+pub enum SortField {
+    Created,
+    Updated,
+    Published,
+    Title,
+}
+
+impl SortField {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "created" => Some(SortField::Created),
+            "updated" => Some(SortField::Updated),
+            "published" => Some(SortField::Published),
+            "title" => Some(SortField::Title),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SortField::Created => "created",
+            SortField::Updated => "updated",
+            SortField::Published => "published",
+            SortField::Title => "title",
+        }
+    }
+}
+
+// What happened to a piece of text between two revisions
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+// Granularity of a revision diff, ?mode=line or ?mode=word
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffMode {
+    Line,
+    Word,
+}
+
+impl DiffMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "line" => Some(DiffMode::Line),
+            "word" => Some(DiffMode::Word),
+            _ => None,
+        }
+    }
+}
+
+// Direction of the article listing in the GraphQL API, the REST routes take ?order=asc|desc
+#[derive(Clone, Copy, PartialEq, Eq, Debug, async_graphql::Enum)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
@@ -0,0 +1,103 @@
+use actix_web::http::header::VARY;
+use actix_web::web::{Data, Json, Path, Query, ReqData};
+use actix_web::{HttpRequest, HttpResponse};
+use chrono::Utc;
+use sqlx::PgPool;
+use crate::entities::{ArticleEntity, ArticleListItem, Claims, PinRequest};
+use crate::enums::ArticleStatus;
+use crate::i18n::{requested_languages, LanguageQuery};
+use crate::listing::CURRENTLY_PINNED;
+use crate::review::require_editor;
+use crate::services::localize_articles;
+use crate::utils::log_with_colors;
+use crate::AppState;
+
+// Articles pinned by an editor, in their order. A pin past its pinned_until is
+// simply no longer matched, the article drops back to its place without a cleanup job.
+pub async fn load_featured(db: &PgPool, languages: &[String]) -> Result<Vec<ArticleListItem>, sqlx::Error> {
+    let articles = sqlx::query_as::<_, ArticleEntity>(
+        &format!("SELECT * FROM articles WHERE status = $1 AND {} ORDER BY pinned_position, id", CURRENTLY_PINNED)
+    )
+        .bind::<i32>(ArticleStatus::Published.into())
+        .fetch_all(db)
+        .await?;
+
+    localize_articles(db, articles, languages).await
+}
+
+pub fn validate_pin(request: &PinRequest) -> Result<(), String> {
+    if request.position < 1 {
+        return Err("Position starts at 1".to_string());
+    }
+    if request.pinned_until.is_some_and(|until| until <= Utc::now()) {
+        return Err("pinned_until is in the past".to_string());
+    }
+    Ok(())
+}
+
+// None when there is no such article
+pub async fn set_pin(db: &PgPool, id: i32, pin: Option<&PinRequest>) -> Result<Option<ArticleEntity>, sqlx::Error> {
+    sqlx::query_as::<_, ArticleEntity>(
+        "UPDATE articles SET pinned_position = $1, pinned_until = $2, version = version + 1 WHERE id = $3 RETURNING *"
+    )
+        .bind(pin.map(|pin| pin.position))
+        .bind(pin.and_then(|pin| pin.pinned_until))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+//#[get("/articles/featured")]
+pub async fn fetch_featured(req: HttpRequest, state: Data<AppState>, query: Query<LanguageQuery>) -> HttpResponse {
+    let languages = requested_languages(&req, &query);
+
+    match load_featured(&state.db, &languages).await {
+        Ok(articles) => {
+            log_with_colors("INFO", "GET 200 /articles/featured");
+            HttpResponse::Ok().insert_header((VARY, "Accept-Language")).json(articles)
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to load featured articles")
+        }
+    }
+}
+
+fn respond_with_pin(result: Result<Option<ArticleEntity>, sqlx::Error>, method: &str) -> HttpResponse {
+    match result {
+        Ok(Some(article)) => {
+            log_with_colors("INFO", &format!("{} 200 /articles/{{id}}/pin", method));
+            HttpResponse::Ok().json(article)
+        }
+        Ok(None) => {
+            log_with_colors("WARN", &format!("{} 404 /articles/{{id}}/pin", method));
+            HttpResponse::NotFound().body("Article not found")
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Failed to pin article: {}", e));
+            HttpResponse::InternalServerError().body("Failed to pin article")
+        }
+    }
+}
+
+// Pinning an article that is already pinned moves it
+//#[put("/articles/{id}/pin")]
+pub async fn pin_article(state: Data<AppState>, claims: ReqData<Claims>, id: Path<i32>, request: Json<PinRequest>) -> HttpResponse {
+    if let Err(response) = require_editor(&state.db, &claims.sub, "PUT /articles/{id}/pin").await {
+        return response;
+    }
+    if let Err(message) = validate_pin(&request) {
+        log_with_colors("WARN", "PUT 400 /articles/{id}/pin");
+        return HttpResponse::BadRequest().body(message);
+    }
+
+    respond_with_pin(set_pin(&state.db, id.into_inner(), Some(&request)).await, "PUT")
+}
+
+//#[delete("/articles/{id}/pin")]
+pub async fn unpin_article(state: Data<AppState>, claims: ReqData<Claims>, id: Path<i32>) -> HttpResponse {
+    if let Err(response) = require_editor(&state.db, &claims.sub, "DELETE /articles/{id}/pin").await {
+        return response;
+    }
+    respond_with_pin(set_pin(&state.db, id.into_inner(), None).await, "DELETE")
+}
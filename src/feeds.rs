@@ -0,0 +1,385 @@
+use std::collections::BTreeMap;
+use std::env;
+use actix_web::web::{Data, Query};
+use actix_web::{HttpRequest, HttpResponse};
+use atom_syndication::{ContentBuilder, EntryBuilder, FeedBuilder, LinkBuilder, PersonBuilder, Text};
+use chrono::{DateTime, Utc};
+use rss::{CategoryBuilder, ChannelBuilder, GuidBuilder, ItemBuilder};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use crate::entities::{ArticleEntity, FeedArticle, TagEntity};
+use crate::enums::{ArticleStatus, ArticleType};
+use crate::site::Links;
+use crate::tags::{load_article_tags, tags_of};
+use crate::utils::{cached_response, log_with_colors, markdown_to_html, read_article_markdown, site_title, site_url};
+use crate::AppState;
+
+const DEFAULT_FEED_LIMIT: usize = 20;
+
+#[derive(Deserialize)]
+pub struct FeedQuery {
+    #[serde(rename = "type")]
+    article_type: Option<String>,
+    content: Option<String>, // "full" (default) or "summary"
+    page: Option<usize>,     // only used by the JSON feed
+}
+
+pub struct FeedOptions {
+    pub(crate) article_type: Option<ArticleType>,
+    pub(crate) full_content: bool,
+}
+
+impl FeedOptions {
+    fn from_query(query: &FeedQuery) -> Result<Self, String> {
+        let article_type = match &query.article_type {
+            Some(slug) => Some(ArticleType::from_slug(slug).ok_or_else(|| format!("Unknown article type {}", slug))?),
+            None => None,
+        };
+        let full_content = match query.content.as_deref() {
+            None | Some("full") => true,
+            Some("summary") => false,
+            Some(other) => return Err(format!("Unknown content mode {}, expected full or summary", other)),
+        };
+
+        Ok(FeedOptions { article_type, full_content })
+    }
+
+    fn title(&self) -> String {
+        match self.article_type {
+            Some(article_type) => format!("{} - {}", site_title(), article_type.slug()),
+            None => site_title(),
+        }
+    }
+
+    // Query string that reproduces these options, for self links
+    fn query_string(&self) -> String {
+        self.query_string_with(Vec::new())
+    }
+
+    fn query_string_with(&self, mut params: Vec<String>) -> String {
+        if let Some(article_type) = self.article_type {
+            params.push(format!("type={}", article_type.slug()));
+        }
+        if !self.full_content {
+            params.push("content=summary".to_string());
+        }
+
+        if params.is_empty() {
+            String::new()
+        } else {
+            format!("?{}", params.join("&"))
+        }
+    }
+}
+
+// JSON Feed 1.1, https://www.jsonfeed.org/version/1.1/
+#[derive(Serialize)]
+struct JsonFeed {
+    version: &'static str,
+    title: String,
+    home_page_url: String,
+    feed_url: String,
+    description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_url: Option<String>,
+    authors: Vec<JsonFeedAuthor>,
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedAuthor {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem {
+    id: String,
+    url: String,
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_text: Option<String>,
+    summary: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_published: Option<String>,
+    date_modified: String,
+    tags: Vec<String>,
+    // Items without an author fall back to the authors of the feed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<JsonFeedAuthor>,
+}
+
+pub struct FeedEntry {
+    pub(crate) article: ArticleEntity,
+    pub(crate) content_html: Option<String>,
+    pub(crate) updated: DateTime<Utc>,
+    pub(crate) tags: Vec<TagEntity>,
+    pub(crate) author: Option<String>,
+}
+
+// Users have no display name, the part of the email before the @ stands in for it
+// so the feed does not publish addresses
+fn author_name(email: &str) -> String {
+    email.split('@').next().unwrap_or(email).to_string()
+}
+
+// Entries per feed page, FEED_LIMIT overrides it
+fn feed_limit() -> usize {
+    env::var("FEED_LIMIT")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_FEED_LIMIT)
+        .max(1)
+}
+
+// Stable across renames and URL changes, unlike the article link
+fn entry_id(id: i32) -> String {
+    format!("urn:hephaestus-blog:article:{}", id)
+}
+
+// Newest articles first, with their markdown rendered when the full content is requested
+pub async fn load_feed_entries(db: &PgPool, options: &FeedOptions) -> Result<Vec<FeedEntry>, sqlx::Error> {
+    Ok(load_feed_page(db, options, 1).await?.0)
+}
+
+// One page of feed_limit() entries, and whether older entries follow it.
+// Ordered by publication, editing an old article does not bring it back to the top of readers' feeds.
+pub async fn load_feed_page(db: &PgPool, options: &FeedOptions, page: usize) -> Result<(Vec<FeedEntry>, bool), sqlx::Error> {
+    let limit = feed_limit() as i64;
+    let offset = (page.saturating_sub(1) as i64).saturating_mul(limit);
+
+    // One extra row tells whether there is an older page
+    let mut articles = sqlx::query_as::<_, FeedArticle>(
+        r#"
+        SELECT articles.*, users.email AS author_email
+        FROM articles
+        LEFT JOIN users ON users.id = articles.author_id
+        WHERE articles.status = $1 AND ($2::INTEGER IS NULL OR articles.article_type = $2)
+        ORDER BY articles.published_at DESC NULLS LAST, articles.id DESC
+        LIMIT $3 OFFSET $4
+        "#
+    )
+        .bind::<i32>(ArticleStatus::Published.into())
+        .bind(options.article_type.map(i32::from))
+        .bind(limit + 1)
+        .bind(offset)
+        .fetch_all(db)
+        .await?;
+
+    let has_more = articles.len() as i64 > limit;
+    articles.truncate(limit as usize);
+    let ids: Vec<i32> = articles.iter().map(|feed_article| feed_article.article.id).collect();
+    let tags = load_article_tags(db, &ids).await?;
+
+    // Only the markdown of the requested page is read from disk
+    let entries = articles
+        .into_iter()
+        .map(|FeedArticle { article, author_email }| {
+            let content_html = if options.full_content {
+                let md_contents = read_article_markdown(article.id).unwrap_or_default();
+                Some(markdown_to_html(&md_contents))
+            } else {
+                None
+            };
+
+            let updated = article.updated_at;
+            let tags = tags_of(article.id, &tags);
+            let author = author_email.as_deref().map(author_name);
+            FeedEntry { article, content_html, updated, tags, author }
+        })
+        .collect();
+
+    Ok((entries, has_more))
+}
+
+pub fn last_updated(entries: &[FeedEntry]) -> Option<DateTime<Utc>> {
+    entries.iter().map(|entry| entry.updated).max()
+}
+
+pub fn build_rss(entries: &[FeedEntry], options: &FeedOptions, links: &Links) -> String {
+    let items: Vec<rss::Item> = entries
+        .iter()
+        .map(|entry| {
+            ItemBuilder::default()
+                .title(entry.article.title.clone())
+                .link(links.absolute_article(&entry.article.slug))
+                .description(entry.article.description.clone())
+                .content(entry.content_html.clone())
+                .pub_date(entry.article.published_at.unwrap_or(entry.updated).to_rfc2822())
+                .categories(entry.tags.iter().map(|tag| CategoryBuilder::default().name(tag.name.clone()).build()).collect::<Vec<_>>())
+                .guid(GuidBuilder::default()
+                    .value(entry_id(entry.article.id))
+                    .permalink(false)
+                    .build())
+                .build()
+        })
+        .collect();
+
+    let mut namespaces = BTreeMap::new();
+    namespaces.insert("content".to_string(), "http://purl.org/rss/1.0/modules/content/".to_string());
+
+    ChannelBuilder::default()
+        .title(options.title())
+        .link(site_url())
+        .description(format!("News and announcements from {}", site_title()))
+        .last_build_date(last_updated(entries).map(|updated| updated.to_rfc2822()))
+        .namespaces(namespaces)
+        .items(items)
+        .build()
+        .to_string()
+}
+
+pub fn build_atom(entries: &[FeedEntry], options: &FeedOptions, links: &Links) -> String {
+    let atom_entries: Vec<atom_syndication::Entry> = entries
+        .iter()
+        .map(|entry| {
+            EntryBuilder::default()
+                .id(entry_id(entry.article.id))
+                .title(Text::plain(entry.article.title.clone()))
+                .updated(entry.updated)
+                .published(entry.article.published_at.map(Into::into))
+                .summary(Some(Text::plain(entry.article.description.clone())))
+                .categories(entry.tags.iter().map(|tag| {
+                    atom_syndication::CategoryBuilder::default()
+                        .term(tag.slug.clone())
+                        .label(Some(tag.name.clone()))
+                        .build()
+                }).collect::<Vec<_>>())
+                .content(entry.content_html.clone().map(|html| {
+                    ContentBuilder::default()
+                        .value(Some(html))
+                        .content_type(Some("html".to_string()))
+                        .build()
+                }))
+                .link(LinkBuilder::default()
+                    .href(links.absolute_article(&entry.article.slug))
+                    .rel("alternate")
+                    .build())
+                .build()
+        })
+        .collect();
+
+    FeedBuilder::default()
+        .id(format!("{}/atom.xml{}", site_url(), options.query_string()))
+        .title(Text::plain(options.title()))
+        .updated(last_updated(entries).unwrap_or(DateTime::UNIX_EPOCH))
+        .author(PersonBuilder::default().name(site_title()).build())
+        .link(LinkBuilder::default()
+            .href(format!("{}/atom.xml{}", site_url(), options.query_string()))
+            .rel("self")
+            .build())
+        .link(LinkBuilder::default()
+            .href(site_url())
+            .rel("alternate")
+            .build())
+        .entries(atom_entries)
+        .build()
+        .to_string()
+}
+
+pub fn build_json_feed(entries: &[FeedEntry], options: &FeedOptions, links: &Links, page: usize, has_more: bool) -> String {
+    let items = entries
+        .iter()
+        .map(|entry| JsonFeedItem {
+            id: entry_id(entry.article.id),
+            url: links.absolute_article(&entry.article.slug),
+            title: entry.article.title.clone(),
+            content_html: entry.content_html.clone(),
+            // An item needs one of the two contents, summary mode falls back to the description
+            content_text: entry.content_html.is_none().then(|| entry.article.description.clone()),
+            summary: entry.article.description.clone(),
+            image: links.absolute_photo(entry.article.id),
+            date_published: entry.article.published_at.map(|published| published.to_rfc3339()),
+            date_modified: entry.updated.to_rfc3339(),
+            tags: entry.tags.iter().map(|tag| tag.name.clone()).collect(),
+            authors: entry.author.iter().map(|name| JsonFeedAuthor { name: name.clone() }).collect(),
+        })
+        .collect();
+
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: options.title(),
+        home_page_url: site_url(),
+        feed_url: format!("{}/feed.json{}", site_url(), options.query_string()),
+        description: format!("News and announcements from {}", site_title()),
+        next_url: has_more.then(|| format!(
+            "{}/feed.json{}",
+            site_url(),
+            options.query_string_with(vec![format!("page={}", page + 1)])
+        )),
+        authors: vec![JsonFeedAuthor { name: site_title() }],
+        items,
+    };
+
+    serde_json::to_string(&feed).expect("Failed to serialize JSON feed")
+}
+
+//#[get("/feed.xml")]
+pub async fn rss_feed(req: HttpRequest, state: Data<AppState>, query: Query<FeedQuery>) -> HttpResponse {
+    let options = match FeedOptions::from_query(&query) {
+        Ok(options) => options,
+        Err(message) => {
+            log_with_colors("WARN", "GET 400 /feed.xml");
+            return HttpResponse::BadRequest().body(message);
+        }
+    };
+
+    match load_feed_entries(&state.db, &options).await {
+        Ok(entries) => {
+            log_with_colors("INFO", "GET 200 /feed.xml");
+            cached_response(&req, "application/rss+xml; charset=utf-8", build_rss(&entries, &options, &Links::Server), last_updated(&entries))
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to build feed")
+        }
+    }
+}
+
+//#[get("/atom.xml")]
+pub async fn atom_feed(req: HttpRequest, state: Data<AppState>, query: Query<FeedQuery>) -> HttpResponse {
+    let options = match FeedOptions::from_query(&query) {
+        Ok(options) => options,
+        Err(message) => {
+            log_with_colors("WARN", "GET 400 /atom.xml");
+            return HttpResponse::BadRequest().body(message);
+        }
+    };
+
+    match load_feed_entries(&state.db, &options).await {
+        Ok(entries) => {
+            log_with_colors("INFO", "GET 200 /atom.xml");
+            cached_response(&req, "application/atom+xml; charset=utf-8", build_atom(&entries, &options, &Links::Server), last_updated(&entries))
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to build feed")
+        }
+    }
+}
+
+//#[get("/feed.json")]
+pub async fn json_feed(req: HttpRequest, state: Data<AppState>, query: Query<FeedQuery>) -> HttpResponse {
+    let options = match FeedOptions::from_query(&query) {
+        Ok(options) => options,
+        Err(message) => {
+            log_with_colors("WARN", "GET 400 /feed.json");
+            return HttpResponse::BadRequest().body(message);
+        }
+    };
+    let page = query.page.unwrap_or(1).max(1);
+
+    match load_feed_page(&state.db, &options, page).await {
+        Ok((entries, has_more)) => {
+            log_with_colors("INFO", "GET 200 /feed.json");
+            cached_response(&req, "application/feed+json", build_json_feed(&entries, &options, &Links::Server, page, has_more), last_updated(&entries))
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to build feed")
+        }
+    }
+}
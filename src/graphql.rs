@@ -0,0 +1,776 @@
+use std::env;
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::web::{Data, Json};
+use actix_web::{HttpRequest, HttpResponse};
+use async_graphql::{Context, EmptySubscription, Error, ErrorExtensions, Guard, InputObject, Object, Result, Schema, SimpleObject, Value};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use crate::entities::{ArticleCreateRequest, ArticleEntity, ArticleListItem, ArticleUpdateRequest, ArticleWriteResponse, Claims, LintWarning, PinRequest, RelatedArticle, TagEntity, User};
+use crate::enums::{ArticleStatus, ArticleType, LintRule, SortField, SortOrder, UserRole};
+use crate::featured::{load_featured, set_pin, validate_pin};
+use crate::i18n::{normalize_language, requested_languages, LanguageQuery};
+use crate::listing::{ArticleListing, DateRange};
+use crate::pagination::{Cursor, PageRequest, DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT};
+use crate::related::load_related;
+use crate::review::{load_user_role, may_read};
+use crate::services::{load_localized_article, load_localized_articles, save_article_update, save_new_article, ArticleWriteError};
+use crate::slugs::{self, SlugLookup};
+use crate::trash::trash_article;
+use crate::utils::{article_file_path, article_url, log_with_colors, read_file_contents, validate_jwt};
+
+// POST /graphql, the same data as the REST routes in one round trip.
+// Reading published articles is public like GET /articles, everything else follows
+// the rules of the /protected scope: a JWT, and the editor role where it asks for one.
+
+pub type BlogSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+const DEFAULT_MAX_DEPTH: usize = 10;
+const DEFAULT_MAX_COMPLEXITY: usize = 1000;
+
+// Large enough for the markdown and base64 photo of a createArticle mutation
+pub const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+// Deepest nesting a query may have, GRAPHQL_MAX_DEPTH overrides it
+fn max_depth() -> usize {
+    env::var("GRAPHQL_MAX_DEPTH")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_DEPTH)
+}
+
+// Every field costs one, lists of articles cost their page size times their fields.
+// GRAPHQL_MAX_COMPLEXITY overrides the limit.
+fn max_complexity() -> usize {
+    env::var("GRAPHQL_MAX_COMPLEXITY")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_COMPLEXITY)
+}
+
+pub fn build_schema(db: PgPool) -> BlogSchema {
+    Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .data(db)
+        .limit_depth(max_depth())
+        .limit_complexity(max_complexity())
+        .finish()
+}
+
+// Languages from Accept-Language, a `language` argument overrides them
+struct RequestLanguages(Vec<String>);
+
+fn languages(ctx: &Context<'_>, language: Option<String>) -> Vec<String> {
+    match language.as_deref().and_then(normalize_language) {
+        Some(language) => vec![language],
+        None => ctx.data_unchecked::<RequestLanguages>().0.clone(),
+    }
+}
+
+// Same rule as the /protected scope, a valid JWT
+struct SignedIn;
+
+impl Guard for SignedIn {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        match ctx.data_opt::<Claims>() {
+            Some(_) => Ok(()),
+            None => Err(Error::new("Sign in required").extend_with(|_, e| e.set("code", "UNAUTHORIZED"))),
+        }
+    }
+}
+
+// Editors only, like the handlers that call require_editor
+struct EditorOnly;
+
+impl Guard for EditorOnly {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        SignedIn.check(ctx).await?;
+        let db = ctx.data::<PgPool>()?;
+        match load_user_role(db, &ctx.data::<Claims>()?.sub).await.map_err(database_error)? {
+            (_, UserRole::Editor) => Ok(()),
+            _ => Err(forbidden("Only editors can do this")),
+        }
+    }
+}
+
+fn forbidden(message: &str) -> Error {
+    Error::new(message).extend_with(|_, e| e.set("code", "FORBIDDEN"))
+}
+
+// Id and role of the signed in user, None for anonymous clients
+async fn viewer(ctx: &Context<'_>) -> Result<Option<(Option<i32>, UserRole)>> {
+    let Some(claims) = ctx.data_opt::<Claims>() else {
+        return Ok(None);
+    };
+    let db = ctx.data::<PgPool>()?;
+    load_user_role(db, &claims.sub).await.map(Some).map_err(database_error)
+}
+
+// The article when the client may read it, by the rules of may_read
+async fn readable(ctx: &Context<'_>, article: Option<ArticleListItem>) -> Result<Option<ArticleNode>> {
+    let Some(article) = article else {
+        return Ok(None);
+    };
+    let allowed = match viewer(ctx).await? {
+        Some((user_id, role)) => may_read(user_id, role, &article.article),
+        None => article.article.is_public(),
+    };
+    Ok(Some(article).filter(|_| allowed).map(ArticleNode))
+}
+
+// Database errors are logged, clients only learn that the query failed
+fn database_error(e: sqlx::Error) -> Error {
+    log_with_colors("ERROR", &format!("Database query failed: {}", e));
+    Error::new("Database query failed").extend_with(|_, e| e.set("code", "INTERNAL"))
+}
+
+fn write_error(error: ArticleWriteError) -> Error {
+    let code = match &error {
+        ArticleWriteError::Invalid(_) => "BAD_REQUEST",
+        ArticleWriteError::NotFound => "NOT_FOUND",
+        ArticleWriteError::SlugTaken(_) | ArticleWriteError::Conflict(_) => "CONFLICT",
+        ArticleWriteError::Forbidden(_) => "FORBIDDEN",
+        ArticleWriteError::VersionMismatch(_) => "PRECONDITION_FAILED",
+        ArticleWriteError::VersionMissing => "PRECONDITION_REQUIRED",
+        ArticleWriteError::Lint(_) => "LINT_FAILED",
+        ArticleWriteError::Database(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            "INTERNAL"
+        }
+        ArticleWriteError::Storage(e) => {
+            log_with_colors("ERROR", e);
+            "INTERNAL"
+        }
+    };
+
+    let warnings = match &error {
+        ArticleWriteError::Lint(warnings) => serde_json::to_value(warnings).ok().and_then(|json| Value::from_json(json).ok()),
+        _ => None,
+    };
+    let version = match &error {
+        ArticleWriteError::VersionMismatch(version) => Some(*version),
+        _ => None,
+    };
+    Error::new(error.message()).extend_with(|_, e| {
+        e.set("code", code);
+        if let Some(warnings) = &warnings {
+            e.set("warnings", warnings.clone());
+        }
+        if let Some(version) = version {
+            e.set("version", version);
+        }
+    })
+}
+
+fn page_limit(first: Option<i64>) -> i64 {
+    first.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT)
+}
+
+// ARTICLES
+
+#[derive(InputObject, Default)]
+struct ArticleFilter {
+    #[graphql(name = "type")]
+    article_type: Option<ArticleType>,
+    tag: Option<String>, // tag slug
+    status: Option<ArticleStatus>, // published when missing, contributors see their own articles of the others
+}
+
+// A page of a listing, the cursors work like the ones of GET /articles
+#[derive(SimpleObject)]
+#[graphql(name = "ArticlePage")]
+struct ArticlePage {
+    items: Vec<ArticleNode>,
+    next_cursor: Option<String>,
+    prev_cursor: Option<String>,
+    total_count: Option<i64>, // only when asked for with count: true
+}
+
+struct PageArgs {
+    sort: Option<SortField>,
+    order: Option<SortOrder>,
+    first: Option<i64>,
+    after: Option<String>,
+    count: Option<bool>,
+}
+
+async fn load_page(ctx: &Context<'_>, mut listing: ArticleListing, args: PageArgs, languages: &[String]) -> Result<ArticlePage> {
+    listing.pinned_first = listing.pinned_first && args.sort.is_none();
+    listing.sort = args.sort.unwrap_or(listing.sort);
+    listing.descending = args.order.map_or(listing.descending, |order| order == SortOrder::Desc);
+
+    let cursor = match &args.after {
+        Some(value) => Some(Cursor::decode(value).ok_or_else(|| Error::new("Invalid cursor"))?),
+        None => None,
+    };
+    if cursor.as_ref().is_some_and(|cursor| !listing.accepts(cursor)) {
+        return Err(Error::new("The cursor belongs to a different sort order"));
+    }
+    let page = PageRequest { limit: page_limit(args.first), cursor, count: args.count.unwrap_or(false) };
+
+    let db = ctx.data::<PgPool>()?;
+    let articles = load_localized_articles(db, &listing, &page, languages).await.map_err(database_error)?;
+    Ok(ArticlePage {
+        next_cursor: articles.next.as_ref().map(Cursor::encode),
+        prev_cursor: articles.prev.as_ref().map(Cursor::encode),
+        total_count: articles.total,
+        items: articles.items.into_iter().map(ArticleNode).collect(),
+    })
+}
+
+fn listing(filter: ArticleFilter) -> ArticleListing {
+    ArticleListing {
+        article_type: filter.article_type.map(Into::into),
+        tag: filter.tag,
+        author_id: None,
+        status: Some(filter.status.unwrap_or(ArticleStatus::Published).into()),
+        published_in: None,
+        created: DateRange::default(),
+        published: DateRange::default(),
+        sort: SortField::Created,
+        descending: true,
+        pinned_first: true,
+    }
+}
+
+struct ArticleNode(ArticleListItem);
+
+#[Object(name = "Article")]
+impl ArticleNode {
+    async fn id(&self) -> i32 {
+        self.0.article.id
+    }
+
+    async fn slug(&self) -> &str {
+        &self.0.article.slug
+    }
+
+    async fn url(&self) -> String {
+        article_url(&self.0.article.slug)
+    }
+
+    async fn title(&self) -> &str {
+        &self.0.article.title
+    }
+
+    async fn description(&self) -> &str {
+        &self.0.article.description
+    }
+
+    #[graphql(name = "type")]
+    async fn article_type(&self) -> Result<ArticleType> {
+        ArticleType::try_from(self.0.article.article_type).map_err(Error::new)
+    }
+
+    // Language of the title, description and markdown
+    async fn language(&self) -> &str {
+        &self.0.article.language
+    }
+
+    async fn available_languages(&self) -> &[String] {
+        &self.0.available_languages
+    }
+
+    async fn status(&self) -> ArticleStatus {
+        ArticleStatus::from(self.0.article.status)
+    }
+
+    // When a scheduled article goes public
+    async fn publish_at(&self) -> Option<DateTime<Utc>> {
+        self.0.article.publish_at
+    }
+
+    // Raised by every write, updateArticle and deleteArticle have to name the current one
+    async fn version(&self) -> i32 {
+        self.0.article.version
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.article.created_at
+    }
+
+    async fn updated_at(&self) -> DateTime<Utc> {
+        self.0.article.updated_at
+    }
+
+    async fn published_at(&self) -> Option<DateTime<Utc>> {
+        self.0.article.published_at
+    }
+
+    // Position among the featured articles, while pinned
+    async fn pinned_position(&self) -> Option<i32> {
+        self.0.article.pinned_position
+    }
+
+    async fn pinned_until(&self) -> Option<DateTime<Utc>> {
+        self.0.article.pinned_until
+    }
+
+    async fn markdown(&self) -> String {
+        let md_file_path = article_file_path(self.0.article.id, &self.0.article.md_filename);
+        read_file_contents(md_file_path.to_str().expect("Failed to convert markdown file path to string"))
+            .unwrap_or_else(|e| {
+                log_with_colors("ERROR", &format!("Failed to read markdown file: {}", e));
+                String::new()
+            })
+    }
+
+    async fn tags(&self) -> Vec<TagNode> {
+        self.0.tags.iter().cloned().map(TagNode).collect()
+    }
+
+    async fn related(&self, ctx: &Context<'_>) -> Result<Vec<RelatedArticleNode>> {
+        let db = ctx.data::<PgPool>()?;
+        let related = load_related(db, self.0.article.id).await.map_err(database_error)?;
+        Ok(related.into_iter().map(RelatedArticleNode).collect())
+    }
+
+    // Email and role of a user, editors only like the users query
+    #[graphql(guard = "EditorOnly")]
+    async fn author(&self, ctx: &Context<'_>) -> Result<Option<UserNode>> {
+        let Some(author_id) = self.0.article.author_id else {
+            return Ok(None);
+        };
+        let db = ctx.data::<PgPool>()?;
+        let user = sqlx::query_as::<_, User>("SELECT id, email, password_hash, role FROM users WHERE id = $1")
+            .bind(author_id)
+            .fetch_optional(db)
+            .await
+            .map_err(database_error)?;
+        Ok(user.map(UserNode))
+    }
+}
+
+struct RelatedArticleNode(RelatedArticle);
+
+#[Object(name = "RelatedArticle")]
+impl RelatedArticleNode {
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    async fn slug(&self) -> &str {
+        &self.0.slug
+    }
+
+    async fn title(&self) -> &str {
+        &self.0.title
+    }
+
+    async fn description(&self) -> &str {
+        &self.0.description
+    }
+
+    #[graphql(name = "type")]
+    async fn article_type(&self) -> Result<ArticleType> {
+        ArticleType::try_from(self.0.article_type).map_err(Error::new)
+    }
+
+    async fn score(&self) -> f32 {
+        self.0.score
+    }
+
+    async fn article(&self, ctx: &Context<'_>, language: Option<String>) -> Result<Option<ArticleNode>> {
+        let db = ctx.data::<PgPool>()?;
+        let article = load_localized_article(db, self.0.id, &languages(ctx, language)).await.map_err(database_error)?;
+        readable(ctx, article).await
+    }
+}
+
+// TAGS
+
+struct TagNode(TagEntity);
+
+#[Object(name = "Tag")]
+impl TagNode {
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    async fn slug(&self) -> &str {
+        &self.0.slug
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    // Published articles only, like GET /tags
+    async fn article_count(&self, ctx: &Context<'_>) -> Result<i64> {
+        let db = ctx.data::<PgPool>()?;
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM article_tags JOIN articles ON articles.id = article_tags.article_id WHERE article_tags.tag_id = $1 AND articles.status = $2"
+        )
+            .bind(self.0.id)
+            .bind::<i32>(ArticleStatus::Published.into())
+            .fetch_one(db)
+            .await
+            .map_err(database_error)
+    }
+
+    #[graphql(complexity = "page_limit(first) as usize * child_complexity")]
+    #[allow(clippy::too_many_arguments)]
+    async fn articles(
+        &self,
+        ctx: &Context<'_>,
+        sort: Option<SortField>,
+        order: Option<SortOrder>,
+        first: Option<i64>,
+        after: Option<String>,
+        count: Option<bool>,
+        language: Option<String>,
+    ) -> Result<ArticlePage> {
+        let listing = listing(ArticleFilter { tag: Some(self.0.slug.clone()), ..ArticleFilter::default() });
+        let languages = languages(ctx, language);
+        load_page(ctx, listing, PageArgs { sort, order, first, after, count }, &languages).await
+    }
+}
+
+#[derive(SimpleObject)]
+struct ArticleTypeCount {
+    #[graphql(name = "type")]
+    article_type: ArticleType,
+    article_count: i64,
+}
+
+// USERS
+
+struct UserNode(User);
+
+#[Object(name = "User")]
+impl UserNode {
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    async fn email(&self) -> &str {
+        &self.0.email
+    }
+
+    async fn role(&self) -> UserRole {
+        UserRole::from(self.0.role)
+    }
+
+    // Articles the user created
+    #[graphql(complexity = "page_limit(first) as usize * child_complexity")]
+    #[allow(clippy::too_many_arguments)]
+    async fn articles(
+        &self,
+        ctx: &Context<'_>,
+        sort: Option<SortField>,
+        order: Option<SortOrder>,
+        first: Option<i64>,
+        after: Option<String>,
+        count: Option<bool>,
+        language: Option<String>,
+    ) -> Result<ArticlePage> {
+        let listing = ArticleListing { author_id: Some(self.0.id), ..listing(ArticleFilter::default()) };
+        let languages = languages(ctx, language);
+        load_page(ctx, listing, PageArgs { sort, order, first, after, count }, &languages).await
+    }
+}
+
+// QUERIES
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    // Same ordering and filters as GET /articles
+    #[graphql(complexity = "page_limit(first) as usize * child_complexity")]
+    #[allow(clippy::too_many_arguments)]
+    async fn articles(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<ArticleFilter>,
+        sort: Option<SortField>,
+        order: Option<SortOrder>,
+        first: Option<i64>,
+        after: Option<String>,
+        count: Option<bool>,
+        language: Option<String>,
+    ) -> Result<ArticlePage> {
+        let filter = filter.unwrap_or_default();
+        let status = filter.status.unwrap_or(ArticleStatus::Published);
+        let mut listing = listing(filter);
+
+        // The same rules as GET /protected/articles, contributors only list their own articles
+        if status != ArticleStatus::Published {
+            SignedIn.check(ctx).await?;
+            match viewer(ctx).await? {
+                Some((_, UserRole::Editor)) => {}
+                _ if status == ArticleStatus::Trashed => return Err(forbidden("Only editors can see the trash")),
+                Some((Some(user_id), UserRole::Contributor)) => listing.author_id = Some(user_id),
+                _ => return Err(forbidden("Only editors and authors can list unpublished articles")),
+            }
+        }
+        let languages = languages(ctx, language);
+        load_page(ctx, listing, PageArgs { sort, order, first, after, count }, &languages).await
+    }
+
+    // By slug, old slug or id. Articles that are not published only for editors and their author.
+    async fn article(&self, ctx: &Context<'_>, key: String, language: Option<String>) -> Result<Option<ArticleNode>> {
+        let db = ctx.data::<PgPool>()?;
+        let id = match slugs::lookup(db, &key).await.map_err(database_error)? {
+            SlugLookup::Current(id) | SlugLookup::Moved { id, .. } => id,
+            SlugLookup::NotFound => return Ok(None),
+        };
+        let article = load_localized_article(db, id, &languages(ctx, language)).await.map_err(database_error)?;
+        readable(ctx, article).await
+    }
+
+    // Pinned articles in their order, what GET /articles/featured returns
+    async fn featured(&self, ctx: &Context<'_>, language: Option<String>) -> Result<Vec<ArticleNode>> {
+        let db = ctx.data::<PgPool>()?;
+        let articles = load_featured(db, &languages(ctx, language)).await.map_err(database_error)?;
+        Ok(articles.into_iter().map(ArticleNode).collect())
+    }
+
+    async fn tags(&self, ctx: &Context<'_>) -> Result<Vec<TagNode>> {
+        let db = ctx.data::<PgPool>()?;
+        let tags = sqlx::query_as::<_, TagEntity>("SELECT * FROM tags ORDER BY name")
+            .fetch_all(db)
+            .await
+            .map_err(database_error)?;
+        Ok(tags.into_iter().map(TagNode).collect())
+    }
+
+    async fn tag(&self, ctx: &Context<'_>, slug: String) -> Result<Option<TagNode>> {
+        let db = ctx.data::<PgPool>()?;
+        let tag = sqlx::query_as::<_, TagEntity>("SELECT * FROM tags WHERE slug = $1")
+            .bind(slug)
+            .fetch_optional(db)
+            .await
+            .map_err(database_error)?;
+        Ok(tag.map(TagNode))
+    }
+
+    // Every article type with the number of articles of that type
+    async fn article_types(&self, ctx: &Context<'_>) -> Result<Vec<ArticleTypeCount>> {
+        let db = ctx.data::<PgPool>()?;
+        let counts = sqlx::query_as::<_, (i32, i64)>("SELECT article_type, COUNT(*) FROM articles WHERE status = $1 GROUP BY article_type")
+            .bind::<i32>(ArticleStatus::Published.into())
+            .fetch_all(db)
+            .await
+            .map_err(database_error)?;
+
+        Ok([ArticleType::Important, ArticleType::Favourite, ArticleType::Common]
+            .into_iter()
+            .map(|article_type| {
+                let value: i32 = article_type.into();
+                let article_count = counts.iter().find(|(counted, _)| *counted == value).map_or(0, |(_, count)| *count);
+                ArticleTypeCount { article_type, article_count }
+            })
+            .collect())
+    }
+
+    #[graphql(guard = "EditorOnly")]
+    async fn users(&self, ctx: &Context<'_>) -> Result<Vec<UserNode>> {
+        let db = ctx.data::<PgPool>()?;
+        let users = sqlx::query_as::<_, User>("SELECT id, email, password_hash, role FROM users ORDER BY id")
+            .fetch_all(db)
+            .await
+            .map_err(database_error)?;
+        Ok(users.into_iter().map(UserNode).collect())
+    }
+}
+
+// MUTATIONS
+
+#[derive(InputObject)]
+struct ArticleCreateInput {
+    title: String,
+    description: String,
+    #[graphql(name = "type")]
+    article_type: ArticleType,
+    language: Option<String>, // DEFAULT_LANGUAGE when missing
+    slug: Option<String>,     // generated from the title when missing
+    #[graphql(default)]
+    tags: Vec<String>,
+    markdown: String,
+    photo: Option<String>, // base64 encoded jpeg
+    status: Option<ArticleStatus>, // published, or scheduled when publishAt is set
+    publish_at: Option<DateTime<Utc>>,
+}
+
+// Fields left out keep their current value
+#[derive(InputObject)]
+struct ArticleUpdateInput {
+    title: Option<String>,
+    description: Option<String>,
+    slug: Option<String>,
+    tags: Option<Vec<String>>, // replaces every tag of the article
+    status: Option<ArticleStatus>, // scheduled when only publishAt is set
+    publish_at: Option<DateTime<Utc>>,
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "LintWarning")]
+struct LintWarningNode {
+    rule: LintRule,
+    message: String,
+    line: Option<i32>, // 1-based line in the markdown, missing for the title
+    blocking: bool,
+}
+
+impl From<LintWarning> for LintWarningNode {
+    fn from(warning: LintWarning) -> Self {
+        LintWarningNode {
+            rule: warning.rule,
+            message: warning.message,
+            line: warning.line.map(|line| line as i32),
+            blocking: warning.blocking,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct ArticleWrite {
+    article: ArticleNode,
+    warnings: Vec<LintWarningNode>,
+}
+
+impl ArticleWrite {
+    // The article in the language it was written in
+    async fn from_response(db: &PgPool, response: ArticleWriteResponse) -> Result<Self> {
+        let language = response.article.language.clone();
+        let article = load_localized_article(db, response.article.id, &[language])
+            .await
+            .map_err(database_error)?
+            .unwrap_or(ArticleListItem { article: response.article, available_languages: Vec::new(), tags: response.tags });
+
+        Ok(ArticleWrite {
+            article: ArticleNode(article),
+            warnings: response.warnings.into_iter().map(LintWarningNode::from).collect(),
+        })
+    }
+}
+
+async fn pinned_article(ctx: &Context<'_>, id: i32, pin: Option<&PinRequest>) -> Result<ArticleNode> {
+    let db = ctx.data::<PgPool>()?;
+    let article = set_pin(db, id, pin)
+        .await
+        .map_err(database_error)?
+        .ok_or_else(|| write_error(ArticleWriteError::NotFound))?;
+
+    let languages = languages(ctx, None);
+    let article = load_localized_article(db, article.id, &languages)
+        .await
+        .map_err(database_error)?
+        .ok_or_else(|| write_error(ArticleWriteError::NotFound))?;
+    Ok(ArticleNode(article))
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    #[graphql(guard = "SignedIn")]
+    async fn create_article(&self, ctx: &Context<'_>, input: ArticleCreateInput) -> Result<ArticleWrite> {
+        let db = ctx.data::<PgPool>()?;
+        let claims = ctx.data::<Claims>()?;
+
+        let photo = match &input.photo {
+            Some(photo) => Some(STANDARD.decode(photo).map_err(|_| Error::new("Photo is not valid base64"))?),
+            None => None,
+        };
+        let request = ArticleCreateRequest {
+            title: input.title,
+            description: input.description,
+            article_type: input.article_type.into(),
+            language: input.language,
+            tags: input.tags,
+            slug: input.slug,
+            status: input.status.map(Into::into),
+            publish_at: input.publish_at,
+        };
+
+        let response = save_new_article(db, &request, Some(&input.markdown), photo.as_deref(), &claims.sub)
+            .await
+            .map_err(write_error)?;
+        ArticleWrite::from_response(db, response).await
+    }
+
+    #[graphql(guard = "SignedIn")]
+    async fn update_article(&self, ctx: &Context<'_>, id: i32, version: i32, input: ArticleUpdateInput) -> Result<ArticleWrite> {
+        let db = ctx.data::<PgPool>()?;
+        let claims = ctx.data::<Claims>()?;
+
+        let current = sqlx::query_as::<_, ArticleEntity>("SELECT * FROM articles WHERE id = $1")
+            .bind(id)
+            .fetch_optional(db)
+            .await
+            .map_err(database_error)?
+            .ok_or_else(|| write_error(ArticleWriteError::NotFound))?;
+
+        // An empty slug keeps the current one
+        let update = ArticleUpdateRequest {
+            article: ArticleEntity {
+                title: input.title.unwrap_or(current.title.clone()),
+                description: input.description.unwrap_or(current.description.clone()),
+                slug: input.slug.unwrap_or_default(),
+                ..current
+            },
+            tags: input.tags,
+            status: input.status.map(Into::into),
+            publish_at: input.publish_at,
+        };
+
+        let response = save_article_update(db, id, update, Some(version), &claims.sub)
+            .await
+            .map_err(write_error)?;
+        ArticleWrite::from_response(db, response).await
+    }
+
+    // Pinning an article that is already pinned moves it
+    #[graphql(guard = "EditorOnly")]
+    async fn pin_article(&self, ctx: &Context<'_>, id: i32, position: i32, pinned_until: Option<DateTime<Utc>>) -> Result<ArticleNode> {
+        let pin = PinRequest { position, pinned_until };
+        validate_pin(&pin).map_err(|message| write_error(ArticleWriteError::Invalid(message)))?;
+        pinned_article(ctx, id, Some(&pin)).await
+    }
+
+    #[graphql(guard = "EditorOnly")]
+    async fn unpin_article(&self, ctx: &Context<'_>, id: i32) -> Result<ArticleNode> {
+        pinned_article(ctx, id, None).await
+    }
+
+    // Moves the article to the trash like DELETE /protected/articles/{id}
+    #[graphql(guard = "EditorOnly")]
+    async fn delete_article(&self, ctx: &Context<'_>, id: i32, version: i32) -> Result<bool> {
+        let db = ctx.data::<PgPool>()?;
+        let claims = ctx.data::<Claims>()?;
+        trash_article(db, id, &claims.sub, Some(version)).await.map_err(write_error)?;
+        Ok(true)
+    }
+}
+
+//#[post("/graphql")]
+pub async fn graphql(req: HttpRequest, schema: Data<BlogSchema>, request: Json<async_graphql::Request>) -> HttpResponse {
+    let mut request = request.into_inner();
+
+    // No token reads like an anonymous client, a bad one is refused like in /protected
+    let token = req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|header_value| header_value.to_str().ok())
+        .map(|bearer| bearer.trim_start_matches("Bearer ").to_string());
+    if let Some(token) = token {
+        match validate_jwt(&token) {
+            Ok(claims) => request = request.data(claims),
+            Err(_) => {
+                log_with_colors("WARN", "POST 401 /graphql");
+                return HttpResponse::Unauthorized().finish();
+            }
+        }
+    }
+
+    let languages = requested_languages(&req, &LanguageQuery { lang: None });
+    let response = schema.execute(request.data(RequestLanguages(languages))).await;
+
+    if response.is_ok() {
+        log_with_colors("INFO", "POST 200 /graphql");
+    } else {
+        log_with_colors("WARN", &format!("POST 200 /graphql - {} errors", response.errors.len()));
+    }
+    HttpResponse::Ok().json(response)
+}
@@ -0,0 +1,68 @@
+use std::env;
+use actix_web::http::header::ACCEPT_LANGUAGE;
+use actix_web::HttpRequest;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct LanguageQuery {
+    pub(crate) lang: Option<String>,
+}
+
+// Language of articles created without one
+pub fn default_language() -> String {
+    env::var("DEFAULT_LANGUAGE").unwrap_or_else(|_| "el".to_string())
+}
+
+// Lowercase a language tag such as "en" or "en-GB", None when it is not one
+pub fn normalize_language(tag: &str) -> Option<String> {
+    let tag = tag.trim().to_lowercase();
+    let mut parts = tag.split('-');
+    let primary = parts.next()?;
+
+    let primary_ok = (2..=3).contains(&primary.len()) && primary.chars().all(|c| c.is_ascii_alphabetic());
+    let rest_ok = parts.all(|part| (1..=8).contains(&part.len()) && part.chars().all(|c| c.is_ascii_alphanumeric()));
+
+    (primary_ok && rest_ok).then_some(tag)
+}
+
+// Languages the client asked for, most preferred first: ?lang= wins over Accept-Language
+pub fn requested_languages(req: &HttpRequest, query: &LanguageQuery) -> Vec<String> {
+    if let Some(lang) = query.lang.as_deref().and_then(normalize_language) {
+        return vec![lang];
+    }
+
+    let header = req.headers()
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+
+    let mut weighted: Vec<(String, f32)> = header
+        .split(',')
+        .filter_map(|part| {
+            let mut pieces = part.split(';');
+            let language = normalize_language(pieces.next()?)?;
+            let quality = pieces
+                .find_map(|piece| piece.trim().strip_prefix("q="))
+                .and_then(|q| q.parse().ok())
+                .unwrap_or(1.0);
+            (quality > 0.0).then_some((language, quality))
+        })
+        .collect();
+
+    // Stable, so equal weights keep the order the client sent them in
+    weighted.sort_by(|a, b| b.1.total_cmp(&a.1));
+    weighted.into_iter().map(|(language, _)| language).collect()
+}
+
+// Pick the best of `available` for the preferences, matching "en-gb" against "en" and back
+pub fn negotiate(preferences: &[String], available: &[String]) -> Option<String> {
+    let primary = |tag: &str| tag.split('-').next().unwrap_or(tag).to_string();
+
+    preferences.iter().find_map(|wanted| {
+        available
+            .iter()
+            .find(|language| *language == wanted)
+            .or_else(|| available.iter().find(|language| primary(language) == primary(wanted)))
+            .cloned()
+    })
+}
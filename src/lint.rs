@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+use std::env;
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use crate::entities::LintWarning;
+use crate::enums::LintRule;
+use crate::utils::log_with_colors;
+
+const DEFAULT_MAX_TITLE_LENGTH: usize = 120;
+
+// Rules listed in LINT_BLOCKING_RULES (comma separated, e.g. "missing-alt-text,raw-html")
+// reject the upload instead of only being reported
+pub fn blocking_rules() -> HashSet<LintRule> {
+    env::var("LINT_BLOCKING_RULES")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|name| !name.trim().is_empty())
+        .filter_map(|name| {
+            let rule = LintRule::from_name(name);
+            if rule.is_none() {
+                log_with_colors("WARN", &format!("Unknown lint rule in LINT_BLOCKING_RULES: {}", name.trim()));
+            }
+            rule
+        })
+        .collect()
+}
+
+fn max_title_length() -> usize {
+    env::var("LINT_MAX_TITLE_LENGTH")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_TITLE_LENGTH)
+}
+
+fn line_of(markdown: &str, offset: usize) -> usize {
+    markdown[..offset].matches('\n').count() + 1
+}
+
+// Run every lint rule against an article title and its markdown body
+pub fn lint_article(title: &str, markdown: &str) -> Vec<LintWarning> {
+    let blocking = blocking_rules();
+    let mut warnings = Vec::new();
+
+    let mut push = |rule: LintRule, message: String, line: Option<usize>| {
+        warnings.push(LintWarning {
+            rule,
+            message,
+            line,
+            blocking: blocking.contains(&rule),
+        });
+    };
+
+    let max_title = max_title_length();
+    let title_length = title.chars().count();
+    if title_length > max_title {
+        push(
+            LintRule::TitleTooLong,
+            format!("Title is {} characters long, the limit is {}", title_length, max_title),
+            None,
+        );
+    }
+
+    // Text collected while inside an image, link or heading
+    let mut image: Option<(usize, String)> = None;
+    let mut link: Option<(usize, String, bool)> = None;
+    let mut heading: Option<(usize, u8, String)> = None;
+
+    let mut previous_level: Option<u8> = None;
+    let mut seen_headings = HashSet::new();
+
+    for (event, range) in Parser::new(markdown).into_offset_iter() {
+        let line = line_of(markdown, range.start);
+
+        match event {
+            Event::Start(Tag::Image { .. }) => image = Some((line, String::new())),
+            Event::End(TagEnd::Image) => {
+                if let Some((line, alt)) = image.take() {
+                    if alt.trim().is_empty() {
+                        push(LintRule::MissingAltText, "Image has no alt text".to_string(), Some(line));
+                    }
+                }
+            }
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                link = Some((line, String::new(), dest_url.trim().is_empty()))
+            }
+            Event::End(TagEnd::Link) => {
+                if let Some((line, text, empty_destination)) = link.take() {
+                    if empty_destination {
+                        push(LintRule::EmptyLink, "Link has no destination".to_string(), Some(line));
+                    } else if text.trim().is_empty() {
+                        push(LintRule::EmptyLink, "Link has no text".to_string(), Some(line));
+                    }
+                }
+            }
+            Event::Start(Tag::Heading { level, .. }) => heading = Some((line, level as u8, String::new())),
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((line, level, text)) = heading.take() {
+                    if let Some(previous) = previous_level {
+                        if level > previous + 1 {
+                            push(
+                                LintRule::HeadingJump,
+                                format!("Heading level jumps from h{} to h{}", previous, level),
+                                Some(line),
+                            );
+                        }
+                    }
+                    previous_level = Some(level);
+
+                    let normalized = text.trim().to_lowercase();
+                    if !seen_headings.insert(normalized) {
+                        push(
+                            LintRule::DuplicateHeading,
+                            format!("Duplicate heading \"{}\"", text.trim()),
+                            Some(line),
+                        );
+                    }
+                }
+            }
+            // A block produces one Html event per line, so report it once at its start
+            Event::Start(Tag::HtmlBlock) | Event::InlineHtml(_) => {
+                push(LintRule::RawHtml, "Raw HTML is not allowed in articles".to_string(), Some(line));
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, alt)) = image.as_mut() {
+                    alt.push_str(&text);
+                }
+                if let Some((_, link_text, _)) = link.as_mut() {
+                    link_text.push_str(&text);
+                }
+                if let Some((_, _, heading_text)) = heading.as_mut() {
+                    heading_text.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    warnings
+}
@@ -0,0 +1,309 @@
+use chrono::{DateTime, Days, NaiveDate, SecondsFormat, Utc};
+use serde::Deserialize;
+use sqlx::{Postgres, QueryBuilder};
+use crate::archive::ArchivePeriod;
+use crate::entities::ArticleEntity;
+use crate::enums::{ArticleStatus, ArticleType, SortField};
+use crate::pagination::Cursor;
+
+// Filters and ordering of GET /articles. Every value reaches the SQL as a bind
+// parameter, only allow-listed column names are ever written into the query text.
+#[derive(Deserialize, Default)]
+pub struct ListQuery {
+    #[serde(rename = "type")]
+    article_type: Option<String>,
+    tag: Option<String>,   // tag slug
+    author: Option<i32>,   // user id of the author
+    // RFC 3339 times or YYYY-MM-DD dates, from is inclusive, a date as to includes that day
+    created_from: Option<String>,
+    created_to: Option<String>,
+    published_from: Option<String>,
+    published_to: Option<String>,
+    sort: Option<String>,  // created (default), updated, published or title
+    order: Option<String>, // asc or desc, desc by default
+}
+
+// Bounds on a date column, `from` inclusive and `to` exclusive
+#[derive(Default)]
+pub struct DateRange {
+    pub(crate) from: Option<DateTime<Utc>>,
+    pub(crate) to: Option<DateTime<Utc>>,
+}
+
+impl DateRange {
+    // `name` is the parameter prefix, such as created, for the error messages
+    fn parse(from: Option<&str>, to: Option<&str>, name: &str) -> Result<Self, String> {
+        let from = from.map(|value| parse_time(value, false).ok_or_else(|| format!("Invalid {}_from {}", name, value))).transpose()?;
+        let to = to.map(|value| parse_time(value, true).ok_or_else(|| format!("Invalid {}_to {}", name, value))).transpose()?;
+        if let (Some(from), Some(to)) = (from, to) {
+            if from >= to {
+                return Err(format!("{}_from has to be before {}_to", name, name));
+            }
+        }
+        Ok(DateRange { from, to })
+    }
+
+    // `column` is one of the allow-listed date columns, the bounds are bound parameters
+    fn push(&self, builder: &mut QueryBuilder<Postgres>, column: &'static str) {
+        if let Some(from) = self.from {
+            builder.push(format!(" AND {} >= ", column)).push_bind(from);
+        }
+        if let Some(to) = self.to {
+            builder.push(format!(" AND {} < ", column)).push_bind(to);
+        }
+    }
+}
+
+// A date alone starts at midnight UTC, as an upper bound it ends at the next midnight
+fn parse_time(value: &str, end_of_day: bool) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    let date = if end_of_day { date.checked_add_days(Days::new(1))? } else { date };
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+// Pinned and not expired yet
+pub const CURRENTLY_PINNED: &str = "pinned_position IS NOT NULL AND (pinned_until IS NULL OR pinned_until > now())";
+
+pub struct ArticleListing {
+    pub(crate) article_type: Option<i32>,
+    pub(crate) tag: Option<String>,
+    pub(crate) author_id: Option<i32>,
+    pub(crate) status: Option<i32>, // published for the public listings, any status when None
+    pub(crate) published_in: Option<ArchivePeriod>,
+    pub(crate) created: DateRange,
+    pub(crate) published: DateRange,
+    pub(crate) sort: SortField,
+    pub(crate) descending: bool,
+    pub(crate) pinned_first: bool, // pinned articles lead the first page instead of taking their place in the order
+}
+
+impl ArticleListing {
+    // Pins only apply to the default ordering, a client asking for a sort gets exactly that
+    pub fn from_query(query: &ListQuery) -> Result<Self, String> {
+        Self::with_default_sort(query, SortField::Created)
+            .map(|listing| ArticleListing { pinned_first: query.sort.is_none(), ..listing })
+    }
+
+    // `sort` applies when the query does not name one
+    pub fn with_default_sort(query: &ListQuery, sort: SortField) -> Result<Self, String> {
+        let article_type = match &query.article_type {
+            Some(slug) => Some(ArticleType::from_slug(slug).ok_or_else(|| format!("Unknown article type {}", slug))?.into()),
+            None => None,
+        };
+        let sort = match &query.sort {
+            Some(name) => SortField::from_name(name).ok_or_else(|| format!("Cannot sort by {}", name))?,
+            None => sort,
+        };
+        let descending = match query.order.as_deref() {
+            None | Some("desc") => true,
+            Some("asc") => false,
+            Some(other) => return Err(format!("Unknown order {}, expected asc or desc", other)),
+        };
+
+        let created = DateRange::parse(query.created_from.as_deref(), query.created_to.as_deref(), "created")?;
+        let published = DateRange::parse(query.published_from.as_deref(), query.published_to.as_deref(), "published")?;
+
+        Ok(ArticleListing {
+            article_type,
+            tag: query.tag.clone(),
+            author_id: query.author,
+            status: Some(ArticleStatus::Published.into()),
+            published_in: None,
+            created,
+            published,
+            sort,
+            descending,
+            pinned_first: false,
+        })
+    }
+
+    // Identifies the ordering inside cursors, a cursor is only valid for the ordering it came from
+    pub fn sort_name(&self) -> String {
+        format!("{}.{}", self.sort.name(), if self.descending { "desc" } else { "asc" })
+    }
+
+    // Column holding the sort key, None when the id alone orders the rows.
+    // Ids are handed out in insertion order, so they double as the creation order.
+    // Articles never published sort by their creation, a NULL key would end the keyset paging.
+    fn sort_column(&self) -> Option<&'static str> {
+        match self.sort {
+            SortField::Created => None,
+            SortField::Updated => Some("updated_at"),
+            SortField::Published => Some("COALESCE(published_at, created_at)"),
+            SortField::Title => Some("title"),
+        }
+    }
+
+    // Cursors carry their key as text, cast back to the column type in the keyset condition
+    fn key_cast(&self) -> &'static str {
+        match self.sort {
+            SortField::Updated | SortField::Published => "::TIMESTAMPTZ",
+            _ => "",
+        }
+    }
+
+    // Sort key of an article as stored in a cursor
+    pub fn key_of(&self, article: &ArticleEntity) -> Option<String> {
+        match self.sort {
+            SortField::Created => None,
+            SortField::Updated => Some(article.updated_at.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+            SortField::Published => Some(article.published_at.unwrap_or(article.created_at).to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+            SortField::Title => Some(article.title.clone()),
+        }
+    }
+
+    // A cursor only continues the ordering it was made for, with a key whenever that ordering has one
+    pub fn accepts(&self, cursor: &Cursor) -> bool {
+        cursor.sort == self.sort_name() && cursor.key.is_some() == self.sort_column().is_some()
+    }
+
+    pub fn cursor(&self, article: &ArticleEntity, backwards: bool) -> Cursor {
+        Cursor {
+            id: article.id,
+            key: self.key_of(article),
+            sort: self.sort_name(),
+            backwards,
+        }
+    }
+
+    // Appends the filter conditions, the query must already end in a WHERE clause
+    pub fn push_filters(&self, builder: &mut QueryBuilder<Postgres>) {
+        if let Some(article_type) = self.article_type {
+            builder.push(" AND article_type = ").push_bind(article_type);
+        }
+        if let Some(tag) = &self.tag {
+            builder
+                .push(" AND id IN (SELECT article_tags.article_id FROM article_tags JOIN tags ON tags.id = article_tags.tag_id WHERE tags.slug = ")
+                .push_bind(tag.clone())
+                .push(")");
+        }
+        // Every status but the trash, which has a listing of its own
+        match self.status {
+            Some(status) => builder.push(" AND status = ").push_bind(status),
+            None => builder.push(" AND status <> ").push_bind::<i32>(ArticleStatus::Trashed.into()),
+        };
+        if let Some(author_id) = self.author_id {
+            builder.push(" AND author_id = ").push_bind(author_id);
+        }
+        if let Some(period) = &self.published_in {
+            period.push_range(builder);
+        }
+        self.created.push(builder, "created_at");
+        self.published.push(builder, "published_at");
+    }
+
+    // Appends the keyset condition that starts the page after (or before) the cursor
+    pub fn push_keyset(&self, builder: &mut QueryBuilder<Postgres>, cursor: &Cursor) {
+        let ascending = self.descending == cursor.backwards;
+        let operator = if ascending { ">" } else { "<" };
+
+        match self.sort_column() {
+            Some(column) => {
+                builder
+                    .push(format!(" AND ({}, id) {} (", column, operator))
+                    .push_bind(cursor.key.clone())
+                    .push(self.key_cast())
+                    .push(", ")
+                    .push_bind(cursor.id)
+                    .push(")");
+            }
+            None => {
+                builder.push(format!(" AND id {} ", operator)).push_bind(cursor.id);
+            }
+        }
+    }
+
+    // Appends ORDER BY, reversed when walking backwards so the nearest rows come first
+    pub fn push_order(&self, builder: &mut QueryBuilder<Postgres>, backwards: bool) {
+        let direction = if self.descending != backwards { "DESC" } else { "ASC" };
+
+        match self.sort_column() {
+            Some(column) => builder.push(format!(" ORDER BY {} {}, id {}", column, direction, direction)),
+            None => builder.push(format!(" ORDER BY id {}", direction)),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters(query: ListQuery) -> String {
+        let listing = ArticleListing::from_query(&query).expect("valid query");
+        let mut builder = QueryBuilder::<Postgres>::new("SELECT * FROM articles WHERE TRUE");
+        listing.push_filters(&mut builder);
+        builder.sql().to_string()
+    }
+
+    #[test]
+    fn author_and_date_ranges_are_bound() {
+        let sql = filters(ListQuery {
+            author: Some(7),
+            created_from: Some("2024-01-01".to_string()),
+            published_to: Some("2024-06-30T12:00:00+02:00".to_string()),
+            ..ListQuery::default()
+        });
+        assert_eq!(
+            sql,
+            "SELECT * FROM articles WHERE TRUE AND status = $1 AND author_id = $2 AND created_at >= $3 AND published_at < $4"
+        );
+    }
+
+    #[test]
+    fn a_date_as_upper_bound_includes_that_day() {
+        let range = DateRange::parse(Some("2024-01-01"), Some("2024-01-31"), "created").unwrap();
+        assert_eq!(range.from.unwrap().to_rfc3339(), "2024-01-01T00:00:00+00:00");
+        assert_eq!(range.to.unwrap().to_rfc3339(), "2024-02-01T00:00:00+00:00");
+    }
+
+    #[test]
+    fn times_are_converted_to_utc() {
+        let range = DateRange::parse(Some("2024-03-01T10:00:00+02:00"), None, "published").unwrap();
+        assert_eq!(range.from.unwrap().to_rfc3339(), "2024-03-01T08:00:00+00:00");
+    }
+
+    #[test]
+    fn invalid_and_empty_ranges_are_rejected() {
+        assert!(DateRange::parse(Some("yesterday"), None, "created").is_err());
+        assert!(DateRange::parse(None, Some("2024-13-01"), "created").is_err());
+        assert!(DateRange::parse(Some("2024-02-01"), Some("2024-01-01"), "published").is_err());
+        assert!(ArticleListing::from_query(&ListQuery { created_to: Some("soon".to_string()), ..ListQuery::default() }).is_err());
+    }
+
+    fn keyset(query: ListQuery, cursor: &Cursor) -> String {
+        let listing = ArticleListing::from_query(&query).expect("valid query");
+        let mut builder = QueryBuilder::<Postgres>::new("SELECT * FROM articles WHERE TRUE");
+        listing.push_keyset(&mut builder, cursor);
+        listing.push_order(&mut builder, cursor.backwards);
+        builder.sql().to_string()
+    }
+
+    #[test]
+    fn unpublished_articles_sort_by_creation_when_sorting_by_publication() {
+        let cursor = Cursor { id: 4, key: Some("2024-01-01T00:00:00Z".to_string()), sort: "published.desc".to_string(), backwards: false };
+        assert_eq!(
+            keyset(ListQuery { sort: Some("published".to_string()), ..ListQuery::default() }, &cursor),
+            "SELECT * FROM articles WHERE TRUE AND (COALESCE(published_at, created_at), id) < ($1::TIMESTAMPTZ, $2) ORDER BY COALESCE(published_at, created_at) DESC, id DESC"
+        );
+    }
+
+    #[test]
+    fn a_cursor_whose_key_is_null_is_rejected() {
+        let listing = ArticleListing::from_query(&ListQuery { sort: Some("published".to_string()), ..ListQuery::default() }).unwrap();
+        let cursor = Cursor { id: 4, key: None, sort: "published.desc".to_string(), backwards: false };
+        assert!(!listing.accepts(&cursor));
+        assert!(listing.accepts(&Cursor { key: Some("2024-01-01T00:00:00Z".to_string()), ..cursor.clone() }));
+
+        // The id alone orders by creation, there the key stays empty
+        let listing = ArticleListing::from_query(&ListQuery::default()).unwrap();
+        assert!(listing.accepts(&Cursor { sort: "created.desc".to_string(), ..cursor }));
+    }
+
+    #[test]
+    fn no_filters_leave_only_the_status() {
+        assert_eq!(filters(ListQuery::default()), "SELECT * FROM articles WHERE TRUE AND status = $1");
+    }
+}
@@ -3,12 +3,34 @@ mod utils;
 mod entities;
 mod enums;
 mod auth;
+mod lint;
+mod site;
+mod static_site;
+mod feeds;
+mod sitemap;
+mod metadata;
+mod i18n;
+mod pagination;
+mod listing;
+mod search;
+mod tags;
+mod slugs;
+mod archive;
+mod related;
+mod graphql;
+mod featured;
+mod series;
+mod publishing;
+mod revisions;
+mod review;
+mod previews;
+mod trash;
 
 use actix_web::{App, HttpServer, web::Data};
-use actix_web::web::{delete, get, post, put, route, scope};
+use actix_web::web::{delete, get, post, put, resource, scope, JsonConfig};
 use dotenv::dotenv;
 use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
-use services::{fetch_all_articles, fetch_article, create_article, update_article, delete_article};
+use services::{fetch_all_articles, fetch_article, fetch_article_photo, create_article, update_article, delete_article, upsert_translation, delete_translation};
 use colored::*;
 use crate::services::{login, signup};
 use crate::utils::{create_default_user_if_not_exists, log_with_colors};
@@ -36,8 +58,61 @@ async fn main() -> std::io::Result<()> {
 
     log_with_colors("INFO", "Database migrations added successfully");
 
+    match slugs::assign_missing_slugs(&pool).await {
+        Ok(0) => {}
+        Ok(count) => log_with_colors("INFO", &format!("Generated slugs for {} articles", count)),
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Failed to generate slugs: {}", e));
+            std::process::exit(1);
+        }
+    }
+
+    // `build-static [--output <dir>] [--theme <dir>] [--full]` renders the site to plain files and exits
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("build-static") {
+        if let Err(e) = static_site::build_static(&pool, &args[2..]).await {
+            log_with_colors("ERROR", &format!("Static build failed: {}", e));
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // `reindex-search` refills the full-text search body of every article from its markdown and exits
+    if args.get(1).map(String::as_str) == Some("reindex-search") {
+        match search::reindex_articles(&pool).await {
+            Ok(count) => log_with_colors("INFO", &format!("Reindexed {} articles", count)),
+            Err(e) => {
+                log_with_colors("ERROR", &format!("Search reindex failed: {}", e));
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    // `reindex-related` recomputes the related articles of every article and exits
+    if args.get(1).map(String::as_str) == Some("reindex-related") {
+        match related::refresh_all_related(&pool).await {
+            Ok(count) => log_with_colors("INFO", &format!("Recomputed related articles of {} articles", count)),
+            Err(e) => {
+                log_with_colors("ERROR", &format!("Related articles reindex failed: {}", e));
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
     create_default_user_if_not_exists(&pool).await;
 
+    // Publishes scheduled articles when they are due
+    actix_web::rt::spawn(publishing::run_publisher(pool.clone()));
+    actix_web::rt::spawn(trash::run_purger(pool.clone()));
+
+    // Optional server-rendered website, see site.rs
+    let site = site::Site::from_env().map(Data::new);
+
+    // Built once, every worker shares it
+    let schema = Data::new(graphql::build_schema(pool.clone()));
+
     println!(
         "{}", r#"
  /$$   /$$                     /$$                                       /$$
@@ -57,15 +132,71 @@ async fn main() -> std::io::Result<()> {
             .app_data(Data::new(AppState { db: pool.clone() }))
             .route("/auth/sign-in", post().to(login))
             .route("/articles", get().to(fetch_all_articles))
-            .route("/articles/{article_id}", get().to(fetch_article))
+            .route("/articles/featured", get().to(featured::fetch_featured))
+            .route("/articles/{slug}", get().to(fetch_article))
+            .route("/search", get().to(search::search))
+            .route("/archive", get().to(archive::fetch_archive))
+            .route("/archive/{year:\\d+}", get().to(archive::fetch_archive_year))
+            .route("/archive/{year:\\d+}/{month:\\d+}", get().to(archive::fetch_archive_month))
+            .route("/series", get().to(series::fetch_series_list))
+            .route("/series/{slug}", get().to(series::fetch_series))
+            .route("/tags", get().to(tags::fetch_tags))
+            .route("/tags/{slug}/articles", get().to(tags::fetch_tag_articles))
+            .route("/articles/{article_id}/photo", get().to(fetch_article_photo))
+            .route("/articles/{article_id}/meta", get().to(metadata::fetch_article_meta))
+            .route("/oembed", get().to(metadata::oembed))
+            .route("/feed.xml", get().to(feeds::rss_feed))
+            .route("/atom.xml", get().to(feeds::atom_feed))
+            .route("/feed.json", get().to(feeds::json_feed))
+            .route("/sitemap.xml", get().to(sitemap::sitemap))
+            .route("/sitemap-{page:\\d+}.xml", get().to(sitemap::sitemap_page))
+            .route("/robots.txt", get().to(sitemap::robots))
+            .service(
+                resource("/graphql")
+                    .app_data(schema.clone())
+                    .app_data(JsonConfig::default().limit(graphql::MAX_BODY_SIZE))
+                    .route(post().to(graphql::graphql))
+            )
             .service(
                 scope("/protected")
                     .wrap(auth::Auth)
+                    .route("/articles", get().to(publishing::fetch_editor_articles))
                     .route("/articles", post().to(create_article))
                     .route("/articles/{id}", put().to(update_article))
                     .route("/articles/{id}", delete().to(delete_article))
+                    .route("/articles/{id}/pin", put().to(featured::pin_article))
+                    .route("/articles/{id}/pin", delete().to(featured::unpin_article))
+                    .route("/articles/{id}/submit", post().to(review::submit_article))
+                    .route("/articles/{id}/request-changes", post().to(review::request_changes))
+                    .route("/articles/{id}/approve", post().to(review::approve_article))
+                    .route("/articles/{id}/reviews", get().to(review::fetch_reviews))
+                    .route("/articles/{id}/reviews", post().to(review::add_review_comment))
+                    .route("/review-queue", get().to(review::fetch_review_queue))
+                    .route("/articles/{id}/previews", post().to(previews::create_preview))
+                    .route("/articles/{id}/previews/{preview_id}", delete().to(previews::revoke_preview))
+                    .route("/previews", get().to(previews::fetch_previews))
+                    .route("/trash", get().to(trash::fetch_trash))
+                    .route("/trash/{id}/restore", post().to(trash::restore_article))
+                    .route("/articles/{id}/revisions", get().to(revisions::fetch_revisions))
+                    .route("/articles/{id}/revisions/diff", get().to(revisions::diff_revisions))
+                    .route("/articles/{id}/revisions/{number:\\d+}", get().to(revisions::fetch_revision))
+                    .route("/articles/{id}/revisions/{number:\\d+}/restore", post().to(revisions::restore_article_revision))
+                    .route("/articles/{id}/translations/{lang}", put().to(upsert_translation))
+                    .route("/articles/{id}/translations/{lang}", delete().to(delete_translation))
+                    .route("/tags/{slug}", put().to(tags::rename_tag))
+                    .route("/tags/{slug}/merge", post().to(tags::merge_tag))
+                    .route("/series", post().to(series::create_series))
+                    .route("/series/{slug}", put().to(series::update_series))
+                    .route("/series/{slug}", delete().to(series::delete_series))
+                    .route("/series/{slug}/articles", put().to(series::set_series_parts))
                     .route("/sign-up", post().to(signup))
+                    .route("/users/{id}/role", put().to(review::set_user_role))
             )
+            .configure(|cfg| {
+                if let Some(site) = &site {
+                    site::configure(cfg, site.clone());
+                }
+            })
     })
         .bind(("127.0.0.1", 8080))?
         .run()
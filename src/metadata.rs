@@ -0,0 +1,245 @@
+use actix_web::web::{Data, Path, Query};
+use actix_web::HttpResponse;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::entities::ArticleEntity;
+use crate::enums::ArticleStatus;
+use crate::slugs::{self, SlugLookup};
+use crate::utils::{article_photo_path, article_url, log_with_colors, site_title, site_url};
+use crate::AppState;
+
+// Link preview metadata of an article: OpenGraph and Twitter card tags plus a
+// schema.org NewsArticle, served as JSON and embedded in the HTML article page
+pub struct ArticleMeta {
+    title: String,
+    description: String,
+    url: String,
+    image: Option<String>,
+    published: Option<DateTime<Utc>>,
+    modified: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct ArticleMetaResponse {
+    open_graph: Vec<MetaTag>,
+    twitter: Vec<MetaTag>,
+    json_ld: Value,
+    html: String,
+}
+
+#[derive(Serialize)]
+pub struct MetaTag {
+    property: &'static str,
+    content: String,
+}
+
+#[derive(Deserialize)]
+pub struct OEmbedQuery {
+    url: String,
+    format: Option<String>,
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl ArticleMeta {
+    // `url` and `image` are the absolute URLs of the article and its photo, which differ
+    // between the server (/articles/{id}/photo) and a static build (/media/{id}.jpg)
+    pub fn new(article: &ArticleEntity, url: String, image: Option<String>) -> Self {
+        ArticleMeta {
+            title: article.title.clone(),
+            description: article.description.clone(),
+            url,
+            image,
+            published: article.published_at,
+            modified: Some(article.updated_at),
+        }
+    }
+
+    // Photo URL as served by the API, if the article has a photo
+    pub fn served_image(id: i32) -> Option<String> {
+        article_photo_path(id)
+            .exists()
+            .then(|| format!("{}/articles/{}/photo", site_url(), id))
+    }
+
+    pub fn open_graph(&self) -> Vec<MetaTag> {
+        let mut tags = vec![
+            MetaTag { property: "og:type", content: "article".to_string() },
+            MetaTag { property: "og:site_name", content: site_title() },
+            MetaTag { property: "og:title", content: self.title.clone() },
+            MetaTag { property: "og:description", content: self.description.clone() },
+            MetaTag { property: "og:url", content: self.url.clone() },
+        ];
+        if let Some(image) = &self.image {
+            tags.push(MetaTag { property: "og:image", content: image.clone() });
+        }
+        if let Some(published) = self.published {
+            tags.push(MetaTag { property: "article:published_time", content: published.to_rfc3339() });
+        }
+        if let Some(modified) = self.modified {
+            tags.push(MetaTag { property: "article:modified_time", content: modified.to_rfc3339() });
+        }
+        tags
+    }
+
+    pub fn twitter(&self) -> Vec<MetaTag> {
+        let card = if self.image.is_some() { "summary_large_image" } else { "summary" };
+
+        let mut tags = vec![
+            MetaTag { property: "twitter:card", content: card.to_string() },
+            MetaTag { property: "twitter:title", content: self.title.clone() },
+            MetaTag { property: "twitter:description", content: self.description.clone() },
+        ];
+        if let Some(image) = &self.image {
+            tags.push(MetaTag { property: "twitter:image", content: image.clone() });
+        }
+        tags
+    }
+
+    pub fn json_ld(&self) -> Value {
+        let mut json_ld = json!({
+            "@context": "https://schema.org",
+            "@type": "NewsArticle",
+            "headline": self.title,
+            "description": self.description,
+            "url": self.url,
+            "mainEntityOfPage": self.url,
+            "publisher": {
+                "@type": "Organization",
+                "name": site_title(),
+                "url": site_url(),
+            },
+        });
+        if let Some(image) = &self.image {
+            json_ld["image"] = json!([image]);
+        }
+        if let Some(published) = self.published {
+            json_ld["datePublished"] = json!(published.to_rfc3339());
+        }
+        if let Some(modified) = self.modified {
+            json_ld["dateModified"] = json!(modified.to_rfc3339());
+        }
+        json_ld
+    }
+
+    // Everything that goes into the <head> of the article page
+    pub fn head_html(&self) -> String {
+        let mut html = String::new();
+        html.push_str(&format!("<link rel=\"canonical\" href=\"{}\">\n", html_escape(&self.url)));
+        html.push_str(&format!("<meta name=\"description\" content=\"{}\">\n", html_escape(&self.description)));
+
+        for tag in self.open_graph() {
+            html.push_str(&format!("<meta property=\"{}\" content=\"{}\">\n", tag.property, html_escape(&tag.content)));
+        }
+        for tag in self.twitter() {
+            html.push_str(&format!("<meta name=\"{}\" content=\"{}\">\n", tag.property, html_escape(&tag.content)));
+        }
+
+        html.push_str(&format!(
+            "<link rel=\"alternate\" type=\"application/json+oembed\" href=\"{}\">\n",
+            html_escape(&format!("{}/oembed?url={}&format=json", site_url(), urlencoding::encode(&self.url)))
+        ));
+
+        // "</" would close the script element early
+        let json_ld = self.json_ld().to_string().replace("</", "<\\/");
+        html.push_str(&format!("<script type=\"application/ld+json\">{}</script>\n", json_ld));
+        html
+    }
+
+    pub fn response(&self) -> ArticleMetaResponse {
+        ArticleMetaResponse {
+            open_graph: self.open_graph(),
+            twitter: self.twitter(),
+            json_ld: self.json_ld(),
+            html: self.head_html(),
+        }
+    }
+}
+
+// The slug or id behind an article URL, .../posts/{slug} or .../articles/{slug}
+fn article_key_from_url(url: &str) -> Option<&str> {
+    let path = url.split(['?', '#']).next()?.trim_end_matches('/');
+    let mut segments = path.rsplit('/');
+    let key = segments.next().filter(|key| !key.is_empty())?;
+
+    match segments.next()? {
+        "posts" | "articles" => Some(key),
+        _ => None,
+    }
+}
+
+// Published articles only, the others have nothing to preview
+async fn find_article(state: &AppState, id: i32) -> Result<ArticleEntity, sqlx::Error> {
+    sqlx::query_as::<_, ArticleEntity>(
+        "SELECT * FROM articles WHERE id = $1 AND status = $2"
+    )
+        .bind(id)
+        .bind::<i32>(ArticleStatus::Published.into())
+        .fetch_one(&state.db)
+        .await
+}
+
+//#[get("/articles/{id}/meta")]
+pub async fn fetch_article_meta(state: Data<AppState>, id: Path<i32>) -> HttpResponse {
+    match find_article(&state, id.into_inner()).await {
+        Ok(article) => {
+            let meta = ArticleMeta::new(&article, article_url(&article.slug), ArticleMeta::served_image(article.id));
+
+            log_with_colors("INFO", "GET 200 /articles/{id}/meta");
+            HttpResponse::Ok().json(meta.response())
+        }
+        Err(_) => {
+            log_with_colors("WARN", "GET 404 /articles/{id}/meta");
+            HttpResponse::NotFound().body("Article not found")
+        }
+    }
+}
+
+// oEmbed provider, https://oembed.com
+//#[get("/oembed")]
+pub async fn oembed(state: Data<AppState>, query: Query<OEmbedQuery>) -> HttpResponse {
+    if query.format.as_deref().is_some_and(|format| format != "json") {
+        log_with_colors("WARN", "GET 501 /oembed");
+        return HttpResponse::NotImplemented().body("Only the json format is supported");
+    }
+
+    // Old slugs and numeric ids are followed to the article like a redirect would
+    let id = match article_key_from_url(&query.url) {
+        Some(key) => match slugs::lookup(&state.db, key).await {
+            Ok(SlugLookup::Current(id) | SlugLookup::Moved { id, .. }) => Some(id),
+            _ => None,
+        },
+        None => None,
+    };
+    let article = match id {
+        Some(id) => find_article(&state, id).await.ok(),
+        None => None,
+    };
+
+    match article {
+        Some(article) => {
+            log_with_colors("INFO", "GET 200 /oembed");
+            HttpResponse::Ok().json(json!({
+                "version": "1.0",
+                "type": "link",
+                "title": article.title,
+                "author_name": site_title(),
+                "author_url": site_url(),
+                "provider_name": site_title(),
+                "provider_url": site_url(),
+                "cache_age": 3600,
+            }))
+        }
+        None => {
+            log_with_colors("WARN", "GET 404 /oembed");
+            HttpResponse::NotFound().body("Article not found")
+        }
+    }
+}
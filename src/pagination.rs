@@ -0,0 +1,88 @@
+use actix_web::HttpRequest;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PAGE_LIMIT: i64 = 20;
+pub const MAX_PAGE_LIMIT: i64 = 100;
+
+#[derive(Deserialize)]
+pub struct PageQuery {
+    limit: Option<i64>,
+    cursor: Option<String>,
+    count: Option<bool>, // also return the total number of rows in X-Total-Count
+}
+
+// Position in the listing, handed to clients as an opaque base64 string.
+// It holds the sort key and id of the row it points at, and the ordering it was made for.
+// `backwards` cursors come from a "prev" link and walk towards the start of the listing.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Cursor {
+    pub(crate) id: i32,
+    pub(crate) key: Option<String>,
+    pub(crate) sort: String,
+    pub(crate) backwards: bool,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).expect("Failed to serialize cursor"))
+    }
+
+    pub fn decode(value: &str) -> Option<Cursor> {
+        let bytes = URL_SAFE_NO_PAD.decode(value).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
+pub struct PageRequest {
+    pub(crate) limit: i64,
+    pub(crate) cursor: Option<Cursor>,
+    pub(crate) count: bool,
+}
+
+impl PageRequest {
+    pub fn from_query(query: &PageQuery) -> Result<Self, String> {
+        let cursor = match &query.cursor {
+            Some(value) => Some(Cursor::decode(value).ok_or("Invalid cursor")?),
+            None => None,
+        };
+
+        Ok(PageRequest {
+            limit: query.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT),
+            cursor,
+            count: query.count.unwrap_or(false),
+        })
+    }
+}
+
+pub struct Page<T> {
+    pub(crate) items: Vec<T>,
+    pub(crate) next: Option<Cursor>,
+    pub(crate) prev: Option<Cursor>,
+    pub(crate) total: Option<i64>,
+}
+
+impl<T> Page<T> {
+    // RFC 8288 Link header with the next and prev pages, keeping the other query parameters
+    pub fn link_header(&self, req: &HttpRequest) -> Option<String> {
+        let base_query: Vec<&str> = req.query_string()
+            .split('&')
+            .filter(|param| !param.is_empty() && !param.starts_with("cursor="))
+            .collect();
+
+        let link = |cursor: &Cursor, rel: &str| {
+            let mut query = base_query.clone();
+            let cursor_param = format!("cursor={}", cursor.encode());
+            query.push(&cursor_param);
+            format!("<{}?{}>; rel=\"{}\"", req.path(), query.join("&"), rel)
+        };
+
+        let links: Vec<String> = [(self.next.as_ref(), "next"), (self.prev.as_ref(), "prev")]
+            .into_iter()
+            .filter_map(|(cursor, rel)| cursor.map(|cursor| link(cursor, rel)))
+            .collect();
+
+        (!links.is_empty()).then(|| links.join(", "))
+    }
+}
@@ -0,0 +1,202 @@
+use std::env;
+use actix_web::web::{Data, Json, Path, Query, ReqData};
+use actix_web::HttpResponse;
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+use crate::entities::{Claims, PreviewEntity, PreviewLink, PreviewRequest};
+use crate::enums::{ArticleStatus, UserRole};
+use crate::review::{check_editor_or_author, load_user_role};
+use crate::services::ArticleWriteError;
+use crate::utils::{article_url, generate_preview_token, log_with_colors, validate_preview_token};
+use crate::AppState;
+
+const DEFAULT_PREVIEW_DAYS: i64 = 7;
+const MAX_PREVIEW_DAYS: i64 = 90;
+
+// How long a link lasts when the request does not say, PREVIEW_LINK_DAYS overrides it
+fn default_lifetime() -> Duration {
+    let days = env::var("PREVIEW_LINK_DAYS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_PREVIEW_DAYS);
+    Duration::days(days.clamp(1, MAX_PREVIEW_DAYS))
+}
+
+// ?preview= of GET /articles/{slug}
+#[derive(Deserialize)]
+pub struct PreviewTokenQuery {
+    pub(crate) preview: Option<String>,
+}
+
+// Whether `token` is a preview link of the article that is neither expired nor revoked
+pub async fn preview_allows(db: &PgPool, token: &str, article_id: i32) -> Result<bool, sqlx::Error> {
+    let claims = match validate_preview_token(token) {
+        Ok(claims) if claims.article == article_id => claims,
+        _ => return Ok(false),
+    };
+
+    // A trashed article is not shown, not even to holders of a link
+    sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM article_previews JOIN articles ON articles.id = article_previews.article_id
+            WHERE article_previews.id = $1 AND article_previews.article_id = $2 AND article_previews.revoked_at IS NULL
+                AND article_previews.expires_at > now() AND articles.status <> $3
+        )
+        "#
+    )
+        .bind(claims.preview)
+        .bind(article_id)
+        .bind::<i32>(ArticleStatus::Trashed.into())
+        .fetch_one(db)
+        .await
+}
+
+// Editors share any article, contributors their own. Returns the id of the user.
+async fn check_preview_rights(db: &PgPool, email: &str, article_id: i32) -> Result<Option<i32>, ArticleWriteError> {
+    check_editor_or_author(db, email, article_id, "Only the author and editors can share previews of an article").await
+}
+
+// The token is made again from the stored link, it signs the same claims to the same token
+fn with_token(mut link: PreviewLink) -> Result<PreviewLink, ArticleWriteError> {
+    link.token = generate_preview_token(link.preview.id, link.preview.article_id, link.preview.expires_at)
+        .map_err(|e| ArticleWriteError::Storage(format!("Failed to sign preview token: {}", e)))?;
+    link.url = format!("{}?preview={}", article_url(&link.article_slug), link.token);
+    Ok(link)
+}
+
+async fn save_preview(db: &PgPool, article_id: i32, request: &PreviewRequest, email: &str) -> Result<PreviewLink, ArticleWriteError> {
+    let created_by = check_preview_rights(db, email, article_id).await?;
+
+    let now = Utc::now();
+    let expires_at = match request.expires_at {
+        Some(expires_at) if expires_at <= now => {
+            return Err(ArticleWriteError::Invalid("expires_at has to be in the future".to_string()));
+        }
+        Some(expires_at) if expires_at > now + Duration::days(MAX_PREVIEW_DAYS) => {
+            return Err(ArticleWriteError::Invalid(format!("A preview link lasts at most {} days", MAX_PREVIEW_DAYS)));
+        }
+        Some(expires_at) => expires_at,
+        None => now + default_lifetime(),
+    };
+
+    let link = sqlx::query_as::<_, PreviewLink>(
+        r#"
+        WITH preview AS (
+            INSERT INTO article_previews (article_id, created_by, label, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+        )
+        SELECT preview.*, articles.slug AS article_slug, users.email AS created_by_email
+        FROM preview
+        JOIN articles ON articles.id = preview.article_id
+        LEFT JOIN users ON users.id = preview.created_by
+        "#
+    )
+        .bind(article_id)
+        .bind(created_by)
+        .bind(request.label.trim())
+        .bind(expires_at)
+        .fetch_one(db)
+        .await?;
+
+    with_token(link)
+}
+
+// The link works through GET /articles/{slug}?preview=<token>
+//#[post("/articles/{id}/previews")]
+pub async fn create_preview(
+    state: Data<AppState>,
+    claims: ReqData<Claims>,
+    id: Path<i32>,
+    request: Option<Json<PreviewRequest>>,
+) -> HttpResponse {
+    let request = request.map(Json::into_inner).unwrap_or_default();
+
+    match save_preview(&state.db, id.into_inner(), &request, &claims.sub).await {
+        Ok(link) => {
+            log_with_colors("INFO", "POST 201 /articles/{id}/previews");
+            HttpResponse::Created().json(link)
+        }
+        Err(e) => e.response("POST /articles/{id}/previews"),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PreviewListQuery {
+    article: Option<i32>, // links of one article, of every article when missing
+}
+
+// Links that still work, the newest first. Contributors see the links of their own articles.
+async fn load_previews(db: &PgPool, article_id: Option<i32>, email: &str) -> Result<Vec<PreviewLink>, ArticleWriteError> {
+    let (user_id, role) = load_user_role(db, email).await?;
+    let author_id = match (role, user_id) {
+        (UserRole::Editor, _) => None,
+        (UserRole::Contributor, Some(user_id)) => Some(user_id),
+        (UserRole::Contributor, None) => return Ok(Vec::new()),
+    };
+
+    let links = sqlx::query_as::<_, PreviewLink>(
+        r#"
+        SELECT article_previews.*, articles.slug AS article_slug, users.email AS created_by_email
+        FROM article_previews
+        JOIN articles ON articles.id = article_previews.article_id
+        LEFT JOIN users ON users.id = article_previews.created_by
+        WHERE article_previews.revoked_at IS NULL AND article_previews.expires_at > now()
+            AND ($1::INTEGER IS NULL OR article_previews.article_id = $1)
+            AND ($2::INTEGER IS NULL OR articles.author_id = $2)
+        ORDER BY article_previews.created_at DESC, article_previews.id DESC
+        "#
+    )
+        .bind(article_id)
+        .bind(author_id)
+        .fetch_all(db)
+        .await?;
+    Ok(links)
+}
+
+//#[get("/previews")]
+pub async fn fetch_previews(state: Data<AppState>, claims: ReqData<Claims>, query: Query<PreviewListQuery>) -> HttpResponse {
+    let links = load_previews(&state.db, query.article, &claims.sub).await;
+
+    match links.and_then(|links| links.into_iter().map(with_token).collect::<Result<Vec<_>, _>>()) {
+        Ok(links) => {
+            log_with_colors("INFO", "GET 200 /previews");
+            HttpResponse::Ok().json(links)
+        }
+        Err(e) => e.response("GET /previews"),
+    }
+}
+
+// The token keeps its signature, the link stops working because it is no longer active
+// None when the article has no such active link
+async fn revoke(db: &PgPool, article_id: i32, preview_id: i32, email: &str) -> Result<Option<PreviewEntity>, ArticleWriteError> {
+    check_preview_rights(db, email, article_id).await?;
+
+    sqlx::query_as::<_, PreviewEntity>(
+        "UPDATE article_previews SET revoked_at = now() WHERE id = $1 AND article_id = $2 AND revoked_at IS NULL RETURNING *"
+    )
+        .bind(preview_id)
+        .bind(article_id)
+        .fetch_optional(db)
+        .await
+        .map_err(ArticleWriteError::from)
+}
+
+//#[delete("/articles/{id}/previews/{preview_id}")]
+pub async fn revoke_preview(state: Data<AppState>, claims: ReqData<Claims>, path: Path<(i32, i32)>) -> HttpResponse {
+    let (article_id, preview_id) = path.into_inner();
+
+    match revoke(&state.db, article_id, preview_id, &claims.sub).await {
+        Ok(Some(preview)) => {
+            log_with_colors("INFO", "DELETE 200 /articles/{id}/previews/{preview_id}");
+            HttpResponse::Ok().json(preview)
+        }
+        Ok(None) => {
+            log_with_colors("WARN", "DELETE 404 /articles/{id}/previews/{preview_id}");
+            HttpResponse::NotFound().body("Preview link not found")
+        }
+        Err(e) => e.response("DELETE /articles/{id}/previews/{preview_id}"),
+    }
+}
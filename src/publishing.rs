@@ -0,0 +1,136 @@
+use std::env;
+use std::time::Duration;
+use actix_web::web::{Data, Query, ReqData};
+use actix_web::{HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+use crate::entities::Claims;
+use crate::enums::{ArticleStatus, UserRole};
+use crate::i18n::{requested_languages, LanguageQuery};
+use crate::listing::{ArticleListing, ListQuery};
+use crate::pagination::PageQuery;
+use crate::review::load_user_role;
+use crate::services::respond_with_listing;
+use crate::utils::log_with_colors;
+use crate::AppState;
+
+const DEFAULT_PUBLISHER_INTERVAL: u64 = 30; // seconds
+
+// Status and publish_at an article is stored with, from what the editor asked for.
+// `current` holds the stored ones when updating, None when creating.
+pub fn resolve_status(
+    status: Option<i32>,
+    publish_at: Option<DateTime<Utc>>,
+    current: Option<(ArticleStatus, Option<DateTime<Utc>>)>,
+) -> Result<(ArticleStatus, Option<DateTime<Utc>>), String> {
+    let status = match status {
+        Some(value) if (0..=3).contains(&value) => Some(ArticleStatus::from(value)),
+        Some(4 | 5) => return Err("In review and changes requested are set by submitting and reviewing the article".to_string()),
+        Some(6) => return Err("Articles go to the trash by deleting them".to_string()),
+        Some(value) => return Err(format!("Unknown status {}", value)),
+        None => None,
+    };
+
+    match (status, publish_at) {
+        (None | Some(ArticleStatus::Scheduled), Some(publish_at)) if publish_at <= Utc::now() => {
+            Err("publish_at has to be in the future".to_string())
+        }
+        (None | Some(ArticleStatus::Scheduled), Some(publish_at)) => Ok((ArticleStatus::Scheduled, Some(publish_at))),
+        (Some(ArticleStatus::Scheduled), None) => Err("A scheduled article needs publish_at".to_string()),
+        (Some(_), Some(_)) => Err("publish_at only applies to scheduled articles".to_string()),
+        (Some(status), None) => Ok((status, None)),
+        // Nothing asked for: an update keeps what is stored, a new article is public right away
+        (None, None) => Ok(current.unwrap_or((ArticleStatus::Published, None))),
+    }
+}
+
+// Publishes the scheduled articles that are due, their publication date is the time they were scheduled for
+pub async fn publish_due_articles(db: &PgPool) -> Result<Vec<i32>, sqlx::Error> {
+    sqlx::query_scalar::<_, i32>(
+        r#"
+        UPDATE articles
+        SET status = $1, published_at = COALESCE(published_at, publish_at), publish_at = NULL,
+            updated_at = GREATEST(updated_at, publish_at), version = version + 1
+        WHERE status = $2 AND publish_at <= now()
+        RETURNING id
+        "#
+    )
+        .bind::<i32>(ArticleStatus::Published.into())
+        .bind::<i32>(ArticleStatus::Scheduled.into())
+        .fetch_all(db)
+        .await
+}
+
+// Seconds between two runs of the publisher, PUBLISHER_INTERVAL overrides it
+fn publisher_interval() -> Duration {
+    let seconds = env::var("PUBLISHER_INTERVAL")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_PUBLISHER_INTERVAL);
+    Duration::from_secs(seconds.max(1))
+}
+
+// Runs for as long as the server does. The schedule lives in the database, so
+// articles that fell due while the server was down are published on the first run.
+pub async fn run_publisher(db: PgPool) {
+    let interval = publisher_interval();
+    loop {
+        match publish_due_articles(&db).await {
+            Ok(ids) if ids.is_empty() => {}
+            Ok(ids) => log_with_colors("INFO", &format!("Published scheduled articles {:?}", ids)),
+            Err(e) => log_with_colors("ERROR", &format!("Failed to publish scheduled articles: {}", e)),
+        }
+        actix_web::rt::time::sleep(interval).await;
+    }
+}
+
+#[derive(Deserialize)]
+pub struct StatusQuery {
+    status: Option<String>, // draft, scheduled, published or archived, every status when missing
+}
+
+// The listing of GET /articles for editors, with the articles the public does not see.
+// Contributors get their own articles, the trash is left to editors.
+//#[get("/articles")]
+pub async fn fetch_editor_articles(
+    req: HttpRequest,
+    state: Data<AppState>,
+    claims: ReqData<Claims>,
+    query: Query<LanguageQuery>,
+    page_query: Query<PageQuery>,
+    list_query: Query<ListQuery>,
+    status_query: Query<StatusQuery>,
+) -> HttpResponse {
+    let status = match status_query.status.as_deref() {
+        Some(name) => match ArticleStatus::from_name(name) {
+            Some(status) => Some(status.into()),
+            None => {
+                log_with_colors("WARN", "GET 400 /protected/articles");
+                return HttpResponse::BadRequest().body(format!("Unknown status {}", name));
+            }
+        },
+        None => None,
+    };
+
+    let author_id = match load_user_role(&state.db, &claims.sub).await {
+        Ok((_, UserRole::Editor)) => None,
+        Ok(_) if status == Some(ArticleStatus::Trashed.into()) => {
+            log_with_colors("WARN", "GET 403 /protected/articles - Editors only");
+            return HttpResponse::Forbidden().body("Only editors can see the trash");
+        }
+        Ok((Some(user_id), UserRole::Contributor)) => Some(user_id),
+        Ok((None, UserRole::Contributor)) => {
+            log_with_colors("WARN", "GET 403 /protected/articles - Unknown user");
+            return HttpResponse::Forbidden().body("Only editors and authors can list unpublished articles");
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            return HttpResponse::InternalServerError().body("Failed to load user");
+        }
+    };
+
+    let listing = ArticleListing::from_query(&list_query).map(|listing| ArticleListing { status, author_id: author_id.or(listing.author_id), ..listing });
+    let languages = requested_languages(&req, &query);
+    respond_with_listing(&req, &state.db, &languages, listing, &page_query, "/protected/articles").await
+}
@@ -0,0 +1,186 @@
+use std::collections::HashSet;
+use std::env;
+use sqlx::PgPool;
+use crate::entities::{RelatedArticle, RelationSource};
+use crate::enums::ArticleStatus;
+use crate::utils::slugify;
+
+const DEFAULT_RELATED_LIMIT: i64 = 5;
+
+// A shared tag counts for two points, the same article type for one,
+// and identical wording for four, scaled down by how much the texts overlap
+const TAG_WEIGHT: f32 = 2.0;
+const TYPE_WEIGHT: f32 = 1.0;
+const TEXT_WEIGHT: f32 = 4.0;
+
+// Pairs without a shared tag need at least this much text overlap to be stored,
+// otherwise every article would be related to every other one of its type
+const MIN_TEXT_SIMILARITY: f32 = 0.1;
+
+// How many related articles an article response carries, RELATED_LIMIT overrides it
+pub fn related_limit() -> i64 {
+    env::var("RELATED_LIMIT")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_RELATED_LIMIT)
+}
+
+struct Profile {
+    id: i32,
+    article_type: i32,
+    tags: HashSet<i32>,
+    heading_words: HashSet<String>,
+    body_words: HashSet<String>,
+}
+
+// Words of four letters or more, transliterated and lowercased like slugs so
+// Greek and Latin spellings meet, the length cut drops most stop words
+fn words(text: &str) -> HashSet<String> {
+    slugify(text)
+        .split('-')
+        .filter(|word| word.chars().count() >= 4)
+        .map(String::from)
+        .collect()
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f32 / union as f32
+}
+
+impl Profile {
+    fn new(source: RelationSource) -> Self {
+        Profile {
+            id: source.id,
+            article_type: source.article_type,
+            tags: source.tag_ids.into_iter().collect(),
+            heading_words: words(&format!("{} {}", source.title, source.description)),
+            body_words: words(&source.search_body),
+        }
+    }
+
+    // Symmetric, None when the pair is not worth recommending
+    fn score(&self, other: &Profile) -> Option<f32> {
+        let shared_tags = self.tags.intersection(&other.tags).count() as f32;
+        let text = 0.6 * jaccard(&self.heading_words, &other.heading_words) + 0.4 * jaccard(&self.body_words, &other.body_words);
+        if shared_tags == 0.0 && text < MIN_TEXT_SIMILARITY {
+            return None;
+        }
+
+        let same_type = if self.article_type == other.article_type { 1.0 } else { 0.0 };
+        Some(TAG_WEIGHT * shared_tags + TYPE_WEIGHT * same_type + TEXT_WEIGHT * text)
+    }
+}
+
+// Every article is compared with the one that changed, fine for the size of a school blog
+async fn load_profiles(db: &PgPool) -> Result<Vec<Profile>, sqlx::Error> {
+    let sources = sqlx::query_as::<_, RelationSource>(
+        r#"
+        SELECT articles.id, articles.article_type, articles.title, articles.description, articles.search_body,
+            COALESCE(array_agg(article_tags.tag_id) FILTER (WHERE article_tags.tag_id IS NOT NULL), '{}') AS tag_ids
+        FROM articles
+        LEFT JOIN article_tags ON article_tags.article_id = articles.id
+        GROUP BY articles.id
+        "#
+    )
+        .fetch_all(db)
+        .await?;
+
+    Ok(sources.into_iter().map(Profile::new).collect())
+}
+
+#[derive(Default)]
+struct Relations {
+    article_ids: Vec<i32>,
+    related_ids: Vec<i32>,
+    scores: Vec<f32>,
+}
+
+impl Relations {
+    // Both directions, so either article finds the other
+    fn push_pair(&mut self, a: i32, b: i32, score: f32) {
+        self.article_ids.extend([a, b]);
+        self.related_ids.extend([b, a]);
+        self.scores.extend([score, score]);
+    }
+}
+
+async fn insert_relations(transaction: &mut sqlx::PgConnection, relations: &Relations) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO article_relations (article_id, related_id, score)
+        SELECT * FROM UNNEST($1::INTEGER[], $2::INTEGER[], $3::REAL[])
+        "#
+    )
+        .bind(&relations.article_ids)
+        .bind(&relations.related_ids)
+        .bind(&relations.scores)
+        .execute(transaction)
+        .await?;
+    Ok(())
+}
+
+// Recompute the relations of one article, after it was created or updated
+pub async fn refresh_related(db: &PgPool, article_id: i32) -> Result<(), sqlx::Error> {
+    let profiles = load_profiles(db).await?;
+    let Some(article) = profiles.iter().find(|profile| profile.id == article_id) else {
+        return Ok(());
+    };
+
+    let mut relations = Relations::default();
+    for other in profiles.iter().filter(|other| other.id != article_id) {
+        if let Some(score) = article.score(other) {
+            relations.push_pair(article_id, other.id, score);
+        }
+    }
+
+    let mut transaction = db.begin().await?;
+    sqlx::query("DELETE FROM article_relations WHERE article_id = $1 OR related_id = $1")
+        .bind(article_id)
+        .execute(&mut *transaction)
+        .await?;
+    insert_relations(&mut transaction, &relations).await?;
+    transaction.commit().await
+}
+
+// Recompute every relation, for articles stored before recommendations existed
+pub async fn refresh_all_related(db: &PgPool) -> Result<usize, sqlx::Error> {
+    let profiles = load_profiles(db).await?;
+
+    let mut relations = Relations::default();
+    for (index, article) in profiles.iter().enumerate() {
+        for other in &profiles[index + 1..] {
+            if let Some(score) = article.score(other) {
+                relations.push_pair(article.id, other.id, score);
+            }
+        }
+    }
+
+    let mut transaction = db.begin().await?;
+    sqlx::query("DELETE FROM article_relations").execute(&mut *transaction).await?;
+    insert_relations(&mut transaction, &relations).await?;
+    transaction.commit().await?;
+
+    Ok(profiles.len())
+}
+
+pub async fn load_related(db: &PgPool, article_id: i32) -> Result<Vec<RelatedArticle>, sqlx::Error> {
+    sqlx::query_as::<_, RelatedArticle>(
+        r#"
+        SELECT articles.id, articles.slug, articles.title, articles.description, articles.article_type, article_relations.score
+        FROM article_relations
+        JOIN articles ON articles.id = article_relations.related_id
+        WHERE article_relations.article_id = $1 AND articles.status = $3
+        ORDER BY article_relations.score DESC, articles.id DESC
+        LIMIT $2
+        "#
+    )
+        .bind(article_id)
+        .bind(related_limit())
+        .bind::<i32>(ArticleStatus::Published.into())
+        .fetch_all(db)
+        .await
+}
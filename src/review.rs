@@ -0,0 +1,355 @@
+use actix_web::web::{Data, Json, Path, ReqData};
+use actix_web::HttpResponse;
+use chrono::Utc;
+use sqlx::PgPool;
+use crate::entities::{ArticleEntity, Claims, Review, ReviewEntity, ReviewQueueItem, ReviewRequest, RoleRequest};
+use crate::enums::{ArticleStatus, ReviewAction, UserRole};
+use crate::services::ArticleWriteError;
+use crate::utils::log_with_colors;
+use crate::AppState;
+
+// Id and role of a signed in user. The token of a user that no longer exists gets the least rights.
+pub async fn load_user_role(db: &PgPool, email: &str) -> Result<(Option<i32>, UserRole), sqlx::Error> {
+    let user = sqlx::query_as::<_, (i32, i32)>("SELECT id, role FROM users WHERE email = $1")
+        .bind(email)
+        .fetch_optional(db)
+        .await?;
+
+    Ok(match user {
+        Some((id, role)) => (Some(id), UserRole::from(role)),
+        None => (None, UserRole::Contributor),
+    })
+}
+
+// For the handlers only editors may use, the response to send to anyone else.
+// `route` such as "DELETE /articles/{id}", for the log line.
+pub async fn require_editor(db: &PgPool, email: &str, route: &str) -> Result<(), HttpResponse> {
+    let (method, path) = route.split_once(' ').unwrap_or(("", route));
+    match load_user_role(db, email).await {
+        Ok((_, UserRole::Editor)) => Ok(()),
+        Ok(_) => {
+            log_with_colors("WARN", &format!("{} 403 {} - Editors only", method, path));
+            Err(HttpResponse::Forbidden().body("Only editors can do this"))
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("{} 500 {} - Database query failed: {}", method, path, e));
+            Err(HttpResponse::InternalServerError().body("Failed to load user"))
+        }
+    }
+}
+
+// Published articles are read by everyone, the others by editors and their author. The trash is for editors only.
+pub fn may_read(user_id: Option<i32>, role: UserRole, article: &ArticleEntity) -> bool {
+    match ArticleStatus::from(article.status) {
+        ArticleStatus::Published => true,
+        _ if role == UserRole::Editor => true,
+        ArticleStatus::Trashed => false,
+        _ => user_id.is_some() && article.author_id == user_id,
+    }
+}
+
+// Editors work on every article, contributors on their own. Returns the id of the user.
+// `denied` is what anyone else is told.
+pub async fn check_editor_or_author(db: &PgPool, email: &str, article_id: i32, denied: &str) -> Result<Option<i32>, ArticleWriteError> {
+    let (user_id, role) = load_user_role(db, email).await?;
+    let author_id = sqlx::query_scalar::<_, Option<i32>>("SELECT author_id FROM articles WHERE id = $1")
+        .bind(article_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or(ArticleWriteError::NotFound)?;
+
+    if role != UserRole::Editor && (user_id.is_none() || author_id != user_id) {
+        return Err(ArticleWriteError::Forbidden(denied.to_string()));
+    }
+    Ok(user_id)
+}
+
+// Contributors change their own articles, and only until they are submitted or after changes were requested
+pub fn check_contributor_edit(user_id: Option<i32>, author_id: Option<i32>, current: ArticleStatus) -> Result<(), ArticleWriteError> {
+    if user_id.is_none() || author_id != user_id {
+        return Err(ArticleWriteError::Forbidden("Contributors can only change their own articles".to_string()));
+    }
+    if !matches!(current, ArticleStatus::Draft | ArticleStatus::ChangesRequested) {
+        return Err(ArticleWriteError::Forbidden(format!("The article is {}, only editors can change it", current.name())));
+    }
+    Ok(())
+}
+
+// What a contributor stores stays unpublished, it reaches the public through an approval
+pub fn check_contributor_status(status: ArticleStatus) -> Result<(), ArticleWriteError> {
+    match status {
+        ArticleStatus::Draft | ArticleStatus::ChangesRequested => Ok(()),
+        _ => Err(ArticleWriteError::Forbidden("Contributors save drafts, submit the article for review to publish it".to_string())),
+    }
+}
+
+// Adds an entry to the review history of an article and moves it to the status the action leads to.
+// A comment leaves the status alone. Returns the article as it is afterwards and the new entry.
+pub async fn record_review(
+    db: &PgPool,
+    id: i32,
+    email: &str,
+    action: ReviewAction,
+    request: &ReviewRequest,
+) -> Result<(ArticleEntity, ReviewEntity), ArticleWriteError> {
+    let (user_id, role) = load_user_role(db, email).await?;
+    let (author_id, status) = sqlx::query_as::<_, (Option<i32>, i32)>("SELECT author_id, status FROM articles WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await?
+        .ok_or(ArticleWriteError::NotFound)?;
+    let status = ArticleStatus::from(status);
+    let is_author = user_id.is_some() && author_id == user_id;
+
+    // Who may do it, from which status and where it leads
+    let (verb, allowed_from, to): (&str, &[ArticleStatus], ArticleStatus) = match action {
+        ReviewAction::Comment => {
+            if role != UserRole::Editor && !is_author {
+                return Err(ArticleWriteError::Forbidden("Only the author and editors can comment on an article".to_string()));
+            }
+            if request.comment.trim().is_empty() {
+                return Err(ArticleWriteError::Invalid("A comment needs text".to_string()));
+            }
+            ("comment on", &[], status)
+        }
+        ReviewAction::Submit => {
+            if role != UserRole::Editor && !is_author {
+                return Err(ArticleWriteError::Forbidden("Only the author or an editor can submit an article for review".to_string()));
+            }
+            ("submit", &[ArticleStatus::Draft, ArticleStatus::ChangesRequested], ArticleStatus::InReview)
+        }
+        ReviewAction::RequestChanges => {
+            if role != UserRole::Editor {
+                return Err(ArticleWriteError::Forbidden("Only editors can review articles".to_string()));
+            }
+            if request.comment.trim().is_empty() {
+                return Err(ArticleWriteError::Invalid("Say in the comment what has to change".to_string()));
+            }
+            ("request changes to", &[ArticleStatus::InReview], ArticleStatus::ChangesRequested)
+        }
+        ReviewAction::Approve => {
+            if role != UserRole::Editor {
+                return Err(ArticleWriteError::Forbidden("Only editors can review articles".to_string()));
+            }
+            match request.publish_at {
+                Some(publish_at) if publish_at <= Utc::now() => {
+                    return Err(ArticleWriteError::Invalid("publish_at has to be in the future".to_string()));
+                }
+                Some(_) => ("approve", &[ArticleStatus::InReview], ArticleStatus::Scheduled),
+                None => ("approve", &[ArticleStatus::InReview], ArticleStatus::Published),
+            }
+        }
+    };
+    if !allowed_from.is_empty() && !allowed_from.contains(&status) {
+        return Err(ArticleWriteError::Conflict(format!("Cannot {} an article that is {}", verb, status.name())));
+    }
+
+    let mut transaction = db.begin().await?;
+
+    // The status it was read with is checked again, two reviewers acting at once do not both succeed.
+    // A comment keeps the status and with it the publish_at of a scheduled article.
+    let article = sqlx::query_as::<_, ArticleEntity>(
+        r#"
+        UPDATE articles
+        SET status = $1, publish_at = CASE WHEN $1 = $5 THEN publish_at ELSE $2 END,
+            published_at = CASE WHEN $1 = $3 THEN COALESCE(published_at, now()) ELSE published_at END,
+            version = CASE WHEN $1 = $5 THEN version ELSE version + 1 END
+        WHERE id = $4 AND status = $5
+        RETURNING *
+        "#
+    )
+        .bind::<i32>(to.into())
+        .bind(request.publish_at.filter(|_| to == ArticleStatus::Scheduled))
+        .bind::<i32>(ArticleStatus::Published.into())
+        .bind(id)
+        .bind::<i32>(status.into())
+        .fetch_optional(&mut *transaction)
+        .await?
+        .ok_or_else(|| ArticleWriteError::Conflict("The article changed meanwhile, try again".to_string()))?;
+
+    let review = sqlx::query_as::<_, ReviewEntity>(
+        "INSERT INTO article_reviews (article_id, user_id, action, comment) VALUES ($1, $2, $3, $4) RETURNING *"
+    )
+        .bind(id)
+        .bind(user_id)
+        .bind::<i32>(action.into())
+        .bind(request.comment.trim())
+        .fetch_one(&mut *transaction)
+        .await?;
+
+    transaction.commit().await?;
+    Ok((article, review))
+}
+
+async fn respond_with_transition(state: &AppState, claims: &Claims, id: i32, action: ReviewAction, request: &ReviewRequest, route: &str) -> HttpResponse {
+    match record_review(&state.db, id, &claims.sub, action, request).await {
+        Ok((article, _)) => {
+            let (method, path) = route.split_once(' ').unwrap_or(("", route));
+            log_with_colors("INFO", &format!("{} 200 {}", method, path));
+            HttpResponse::Ok().json(article)
+        }
+        Err(e) => e.response(route),
+    }
+}
+
+// The comment is optional, for a note to the reviewer
+//#[post("/articles/{id}/submit")]
+pub async fn submit_article(
+    state: Data<AppState>,
+    claims: ReqData<Claims>,
+    id: Path<i32>,
+    request: Option<Json<ReviewRequest>>,
+) -> HttpResponse {
+    let request = request.map(Json::into_inner).unwrap_or_default();
+    respond_with_transition(&state, &claims, id.into_inner(), ReviewAction::Submit, &request, "POST /articles/{id}/submit").await
+}
+
+//#[post("/articles/{id}/request-changes")]
+pub async fn request_changes(state: Data<AppState>, claims: ReqData<Claims>, id: Path<i32>, request: Json<ReviewRequest>) -> HttpResponse {
+    respond_with_transition(&state, &claims, id.into_inner(), ReviewAction::RequestChanges, &request, "POST /articles/{id}/request-changes").await
+}
+
+// Publishes the article right away, or schedules it when publish_at is given
+//#[post("/articles/{id}/approve")]
+pub async fn approve_article(
+    state: Data<AppState>,
+    claims: ReqData<Claims>,
+    id: Path<i32>,
+    request: Option<Json<ReviewRequest>>,
+) -> HttpResponse {
+    let request = request.map(Json::into_inner).unwrap_or_default();
+    respond_with_transition(&state, &claims, id.into_inner(), ReviewAction::Approve, &request, "POST /articles/{id}/approve").await
+}
+
+//#[post("/articles/{id}/reviews")]
+pub async fn add_review_comment(state: Data<AppState>, claims: ReqData<Claims>, id: Path<i32>, request: Json<ReviewRequest>) -> HttpResponse {
+    match record_review(&state.db, id.into_inner(), &claims.sub, ReviewAction::Comment, &request).await {
+        Ok((_, review)) => {
+            log_with_colors("INFO", "POST 201 /articles/{id}/reviews");
+            HttpResponse::Created().json(review)
+        }
+        Err(e) => e.response("POST /articles/{id}/reviews"),
+    }
+}
+
+// Comments and transitions, oldest first. For editors and the author, like commenting.
+//#[get("/articles/{id}/reviews")]
+pub async fn fetch_reviews(state: Data<AppState>, claims: ReqData<Claims>, id: Path<i32>) -> HttpResponse {
+    let id = id.into_inner();
+
+    if let Err(e) = check_editor_or_author(&state.db, &claims.sub, id, "Only the author and editors can read the reviews of an article").await {
+        return e.response("GET /articles/{id}/reviews");
+    }
+
+    match sqlx::query_as::<_, Review>(
+        r#"
+        SELECT article_reviews.*, users.email AS user_email
+        FROM article_reviews
+        LEFT JOIN users ON users.id = article_reviews.user_id
+        WHERE article_reviews.article_id = $1
+        ORDER BY article_reviews.created_at, article_reviews.id
+        "#
+    )
+        .bind(id)
+        .fetch_all(&state.db)
+        .await
+    {
+        Ok(reviews) => {
+            log_with_colors("INFO", "GET 200 /articles/{id}/reviews");
+            HttpResponse::Ok().json(reviews)
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to load reviews")
+        }
+    }
+}
+
+// Articles waiting for a reviewer, the longest waiting first
+//#[get("/review-queue")]
+pub async fn fetch_review_queue(state: Data<AppState>, claims: ReqData<Claims>) -> HttpResponse {
+    if let Err(response) = require_editor(&state.db, &claims.sub, "GET /review-queue").await {
+        return response;
+    }
+
+    match sqlx::query_as::<_, ReviewQueueItem>(
+        r#"
+        SELECT articles.*, users.email AS author_email,
+            (SELECT MAX(created_at) FROM article_reviews WHERE article_id = articles.id AND action = $2) AS submitted_at
+        FROM articles
+        LEFT JOIN users ON users.id = articles.author_id
+        WHERE articles.status = $1
+        ORDER BY submitted_at NULLS FIRST, articles.id
+        "#
+    )
+        .bind::<i32>(ArticleStatus::InReview.into())
+        .bind::<i32>(ReviewAction::Submit.into())
+        .fetch_all(&state.db)
+        .await
+    {
+        Ok(articles) => {
+            log_with_colors("INFO", "GET 200 /review-queue");
+            HttpResponse::Ok().json(articles)
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to load the review queue")
+        }
+    }
+}
+
+// There is always one editor left, nobody could review or promote anyone otherwise
+//#[put("/users/{id}/role")]
+pub async fn set_user_role(state: Data<AppState>, claims: ReqData<Claims>, id: Path<i32>, request: Json<RoleRequest>) -> HttpResponse {
+    if let Err(response) = require_editor(&state.db, &claims.sub, "PUT /users/{id}/role").await {
+        return response;
+    }
+    if !(0..=1).contains(&request.role) {
+        log_with_colors("WARN", "PUT 400 /users/{id}/role");
+        return HttpResponse::BadRequest().body(format!("Unknown role {}", request.role));
+    }
+
+    let id = id.into_inner();
+    let updated = sqlx::query_as::<_, (i32, String, i32)>(
+        r#"
+        UPDATE users
+        SET role = $1
+        WHERE id = $2 AND ($1 = $3 OR EXISTS (SELECT 1 FROM users WHERE role = $3 AND id <> $2))
+        RETURNING id, email, role
+        "#
+    )
+        .bind(request.role)
+        .bind(id)
+        .bind::<i32>(UserRole::Editor.into())
+        .fetch_optional(&state.db)
+        .await;
+
+    match updated {
+        Ok(Some((id, email, role))) => {
+            log_with_colors("INFO", "PUT 200 /users/{id}/role");
+            HttpResponse::Ok().json(serde_json::json!({ "id": id, "email": email, "role": role }))
+        }
+        Ok(None) => match sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM users WHERE id = $1)")
+            .bind(id)
+            .fetch_one(&state.db)
+            .await
+        {
+            Ok(true) => {
+                log_with_colors("WARN", "PUT 409 /users/{id}/role");
+                HttpResponse::Conflict().body("This is the last editor")
+            }
+            Ok(false) => {
+                log_with_colors("WARN", "PUT 404 /users/{id}/role");
+                HttpResponse::NotFound().body("User not found")
+            }
+            Err(e) => {
+                log_with_colors("ERROR", &format!("Database query failed: {}", e));
+                HttpResponse::InternalServerError().body("Failed to set role")
+            }
+        },
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Failed to set role: {}", e));
+            HttpResponse::InternalServerError().body("Failed to set role")
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use std::fs::{self, create_dir_all};
+use actix_web::web::{Data, Path, Query, ReqData};
+use actix_web::http::header::ETAG;
+use actix_web::{HttpRequest, HttpResponse};
+use serde::Deserialize;
+use similar::{ChangeTag, TextDiff};
+use sqlx::PgPool;
+use crate::entities::{ArticleEntity, ArticleRevision, ArticleWriteResponse, Claims, DiffChange, RevisionDiff, RevisionSummary};
+use crate::enums::{ArticleStatus, DiffMode, DiffOp, UserRole};
+use crate::lint::lint_article;
+use crate::review::{check_contributor_edit, check_editor_or_author, load_user_role};
+use crate::services::{refresh_related_or_log, required_version, version_mismatch, ArticleWriteError};
+use crate::tags::{load_article_tags, tags_of};
+use crate::utils::{article_etag, article_md_path, log_with_colors, markdown_to_text};
+use crate::AppState;
+
+// Snapshot of the article as stored now, with `markdown` as its text
+pub async fn record_revision(db: &PgPool, article_id: i32, markdown: &str, editor_id: Option<i32>) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar::<_, i32>(
+        r#"
+        INSERT INTO article_revisions (article_id, number, title, description, article_type, markdown, editor_id)
+        SELECT id, COALESCE((SELECT MAX(number) FROM article_revisions WHERE article_id = $1), 0) + 1,
+            title, description, article_type, $2, $3
+        FROM articles
+        WHERE id = $1
+        RETURNING number
+        "#
+    )
+        .bind(article_id)
+        .bind(markdown)
+        .bind(editor_id)
+        .fetch_one(db)
+        .await
+}
+
+// Articles from before revisions existed get their current state as revision 1 before the first change,
+// so that change can be diffed and undone too. The editor of that state is unknown.
+pub async fn record_initial_revision(db: &PgPool, article_id: i32, markdown: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO article_revisions (article_id, number, title, description, article_type, markdown)
+        SELECT id, 1, title, description, article_type, $2
+        FROM articles
+        WHERE id = $1 AND NOT EXISTS (SELECT 1 FROM article_revisions WHERE article_id = $1)
+        "#
+    )
+        .bind(article_id)
+        .bind(markdown)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+async fn load_revision(db: &PgPool, article_id: i32, number: i32) -> Result<Option<ArticleRevision>, sqlx::Error> {
+    sqlx::query_as::<_, ArticleRevision>(
+        r#"
+        SELECT article_revisions.*, users.email AS editor_email
+        FROM article_revisions
+        LEFT JOIN users ON users.id = article_revisions.editor_id
+        WHERE article_revisions.article_id = $1 AND article_revisions.number = $2
+        "#
+    )
+        .bind(article_id)
+        .bind(number)
+        .fetch_optional(db)
+        .await
+}
+
+// The history of an article is read by editors and its author, like they edit it
+async fn check_history_reader(db: &PgPool, email: &str, id: i32) -> Result<(), ArticleWriteError> {
+    check_editor_or_author(db, email, id, "Only the author and editors can read the revisions of an article").await?;
+    Ok(())
+}
+
+//#[get("/articles/{id}/revisions")]
+pub async fn fetch_revisions(state: Data<AppState>, claims: ReqData<Claims>, id: Path<i32>) -> HttpResponse {
+    let id = id.into_inner();
+
+    if let Err(e) = check_history_reader(&state.db, &claims.sub, id).await {
+        return e.response("GET /articles/{id}/revisions");
+    }
+
+    match sqlx::query_as::<_, RevisionSummary>(
+        r#"
+        SELECT article_revisions.number, article_revisions.title, users.email AS editor_email, article_revisions.created_at
+        FROM article_revisions
+        LEFT JOIN users ON users.id = article_revisions.editor_id
+        WHERE article_revisions.article_id = $1
+        ORDER BY article_revisions.number DESC
+        "#
+    )
+        .bind(id)
+        .fetch_all(&state.db)
+        .await
+    {
+        Ok(revisions) => {
+            log_with_colors("INFO", "GET 200 /articles/{id}/revisions");
+            HttpResponse::Ok().json(revisions)
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to load revisions")
+        }
+    }
+}
+
+//#[get("/articles/{id}/revisions/{number}")]
+pub async fn fetch_revision(state: Data<AppState>, claims: ReqData<Claims>, path: Path<(i32, i32)>) -> HttpResponse {
+    let (id, number) = path.into_inner();
+
+    if let Err(e) = check_history_reader(&state.db, &claims.sub, id).await {
+        return e.response("GET /articles/{id}/revisions/{number}");
+    }
+
+    match load_revision(&state.db, id, number).await {
+        Ok(Some(revision)) => {
+            log_with_colors("INFO", "GET 200 /articles/{id}/revisions/{number}");
+            HttpResponse::Ok().json(revision)
+        }
+        Ok(None) => {
+            log_with_colors("WARN", "GET 404 /articles/{id}/revisions/{number}");
+            HttpResponse::NotFound().body("Revision not found")
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to load revision")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    from: i32,
+    to: i32,
+    mode: Option<String>, // line (default) or word
+}
+
+// Consecutive pieces with the same outcome are merged, so the client gets runs of text
+fn diff_text(old: &str, new: &str, mode: DiffMode) -> Vec<DiffChange> {
+    let diff = match mode {
+        DiffMode::Line => TextDiff::from_lines(old, new),
+        DiffMode::Word => TextDiff::from_words(old, new),
+    };
+
+    let mut changes: Vec<DiffChange> = Vec::new();
+    for change in diff.iter_all_changes() {
+        let op = match change.tag() {
+            ChangeTag::Equal => DiffOp::Equal,
+            ChangeTag::Insert => DiffOp::Insert,
+            ChangeTag::Delete => DiffOp::Delete,
+        };
+        match changes.last_mut() {
+            Some(last) if last.op == op => last.text.push_str(change.value()),
+            _ => changes.push(DiffChange { op, text: change.value().to_string() }),
+        }
+    }
+    changes
+}
+
+// Either two revisions can be compared, an older `to` shows the change backwards
+//#[get("/articles/{id}/revisions/diff")]
+pub async fn diff_revisions(state: Data<AppState>, claims: ReqData<Claims>, id: Path<i32>, query: Query<DiffQuery>) -> HttpResponse {
+    let id = id.into_inner();
+
+    if let Err(e) = check_history_reader(&state.db, &claims.sub, id).await {
+        return e.response("GET /articles/{id}/revisions/diff");
+    }
+    let mode = match query.mode.as_deref() {
+        None => DiffMode::Line,
+        Some(name) => match DiffMode::from_name(name) {
+            Some(mode) => mode,
+            None => {
+                log_with_colors("WARN", "GET 400 /articles/{id}/revisions/diff");
+                return HttpResponse::BadRequest().body(format!("Unknown diff mode {}, expected line or word", name));
+            }
+        },
+    };
+
+    let from = load_revision(&state.db, id, query.from).await;
+    let to = load_revision(&state.db, id, query.to).await;
+    let (from, to) = match (from, to) {
+        (Ok(Some(from)), Ok(Some(to))) => (from.revision, to.revision),
+        (Ok(_), Ok(_)) => {
+            log_with_colors("WARN", "GET 404 /articles/{id}/revisions/diff");
+            return HttpResponse::NotFound().body("Revision not found");
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            return HttpResponse::InternalServerError().body("Failed to load revision");
+        }
+    };
+
+    let unified = TextDiff::from_lines(&from.markdown, &to.markdown)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("revision {}", from.number), &format!("revision {}", to.number))
+        .to_string();
+
+    log_with_colors("INFO", "GET 200 /articles/{id}/revisions/diff");
+    HttpResponse::Ok().json(RevisionDiff {
+        from: from.number,
+        to: to.number,
+        mode,
+        title: diff_text(&from.title, &to.title, mode),
+        description: diff_text(&from.description, &to.description, mode),
+        markdown: diff_text(&from.markdown, &to.markdown, mode),
+        unified,
+    })
+}
+
+// Brings back the title, description, type and markdown of an old revision. The restore
+// is a change like any other, it becomes the newest revision and the history stays intact.
+// `expected_version` comes from If-Match like for updates, None for "*".
+pub async fn restore_revision(
+    db: &PgPool,
+    id: i32,
+    number: i32,
+    editor_email: &str,
+    expected_version: Option<i32>,
+) -> Result<ArticleWriteResponse, ArticleWriteError> {
+    let revision = load_revision(db, id, number).await?.ok_or(ArticleWriteError::NotFound)?.revision;
+
+    let (status, author_id, version) = sqlx::query_as::<_, (i32, Option<i32>, i32)>("SELECT status, author_id, version FROM articles WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await?
+        .ok_or(ArticleWriteError::NotFound)?;
+    let status = ArticleStatus::from(status);
+    if status == ArticleStatus::Trashed {
+        return Err(ArticleWriteError::Conflict("The article is in the trash, restore it first".to_string()));
+    }
+    // Checked here to leave the markdown alone, and again by the UPDATE for a write in between
+    if expected_version.is_some_and(|expected| expected != version) {
+        return Err(ArticleWriteError::VersionMismatch(version));
+    }
+
+    // Contributors roll back like they edit, their own articles before review
+    let (editor_id, role) = load_user_role(db, editor_email).await?;
+    if role == UserRole::Contributor {
+        check_contributor_edit(editor_id, author_id, status)?;
+    }
+
+    let md_file_path = article_md_path(id);
+    if let Some(dir) = md_file_path.parent() {
+        create_dir_all(dir).map_err(|e| ArticleWriteError::Storage(format!("Failed to create {}: {}", dir.display(), e)))?;
+    }
+    fs::write(&md_file_path, &revision.markdown)
+        .map_err(|e| ArticleWriteError::Storage(format!("Failed to write {}: {}", md_file_path.display(), e)))?;
+
+    let article = sqlx::query_as::<_, ArticleEntity>(
+        r#"
+        UPDATE articles
+        SET title = $1, description = $2, article_type = $3, search_body = $4, updated_at = now(),
+            version = version + 1
+        WHERE id = $5 AND ($6::INTEGER IS NULL OR version = $6)
+        RETURNING *
+        "#
+    )
+        .bind(&revision.title)
+        .bind(&revision.description)
+        .bind(revision.article_type)
+        .bind(markdown_to_text(&revision.markdown))
+        .bind(id)
+        .bind(expected_version)
+        .fetch_optional(db)
+        .await?;
+    let Some(article) = article else {
+        return Err(version_mismatch(db, id).await);
+    };
+
+    record_revision(db, id, &revision.markdown, editor_id).await?;
+    refresh_related_or_log(db, id).await;
+
+    let tags = tags_of(id, &load_article_tags(db, &[id]).await?);
+    let warnings = lint_article(&article.title, &revision.markdown);
+    Ok(ArticleWriteResponse { article, tags, warnings })
+}
+
+//#[post("/articles/{id}/revisions/{number}/restore")]
+pub async fn restore_article_revision(req: HttpRequest, state: Data<AppState>, claims: ReqData<Claims>, path: Path<(i32, i32)>) -> HttpResponse {
+    let (id, number) = path.into_inner();
+    let expected_version = match required_version(&req) {
+        Ok(version) => version,
+        Err(e) => return e.response("POST /articles/{id}/revisions/{number}/restore"),
+    };
+
+    match restore_revision(&state.db, id, number, &claims.sub, expected_version).await {
+        Ok(response) => {
+            log_with_colors("INFO", "POST 200 /articles/{id}/revisions/{number}/restore");
+            HttpResponse::Ok().insert_header((ETAG, article_etag(response.article.version))).json(response)
+        }
+        Err(e) => e.response("POST /articles/{id}/revisions/{number}/restore"),
+    }
+}
@@ -0,0 +1,129 @@
+use actix_web::web::{Data, Query};
+use actix_web::HttpResponse;
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use crate::entities::{SearchResponse, SearchResult};
+use crate::listing::{ArticleListing, ListQuery};
+use crate::pagination::{DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT};
+use crate::utils::{log_with_colors, markdown_to_text, read_article_markdown};
+use crate::AppState;
+
+// Full-text search over title, description and body. The articles.search_vector column
+// holds both the English and the Greek stemming of the text (see the article_search
+// migration) so a query matches in either language.
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    q: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+const HIGHLIGHT_OPTIONS: &str = "StartSel=<mark>, StopSel=</mark>";
+
+// ts_headline re-parses the text with a single configuration, pick the one of the query's script
+fn headline_config(query: &str) -> &'static str {
+    let greek = query.chars().any(|c| matches!(c, '\u{0370}'..='\u{03FF}' | '\u{1F00}'..='\u{1FFF}'));
+    if greek { "greek" } else { "english" }
+}
+
+pub async fn search_articles(
+    db: &PgPool,
+    query: &str,
+    listing: &ArticleListing,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<SearchResult>, sqlx::Error> {
+    let config = headline_config(query);
+
+    let mut builder = QueryBuilder::<Postgres>::new(
+        "WITH search AS (SELECT websearch_to_tsquery('english', "
+    );
+    builder
+        .push_bind(query)
+        .push(") || websearch_to_tsquery('greek', ")
+        .push_bind(query)
+        .push(") AS q) SELECT articles.*, ts_rank_cd(search_vector, search.q) AS rank, ts_headline(")
+        .push_bind(config)
+        .push("::regconfig, title, search.q, ")
+        .push_bind(format!("{}, HighlightAll=true", HIGHLIGHT_OPTIONS))
+        .push(") AS title_highlight, ts_headline(")
+        .push_bind(config)
+        .push("::regconfig, description || E'\\n' || search_body, search.q, ")
+        .push_bind(format!("{}, MaxFragments=2, MaxWords=30, MinWords=10, FragmentDelimiter=\" … \"", HIGHLIGHT_OPTIONS))
+        .push(") AS snippet, COUNT(*) OVER () AS total FROM articles, search WHERE search_vector @@ search.q");
+
+    // Same filters as GET /articles, results are always ordered by relevance
+    listing.push_filters(&mut builder);
+    builder
+        .push(" ORDER BY rank DESC, id DESC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind(offset);
+
+    builder.build_query_as::<SearchResult>().fetch_all(db).await
+}
+
+// Fills search_body of every article from its markdown file, for articles
+// stored before search existed or whose file was edited on disk. Drafts too, they are searched once published.
+pub async fn reindex_articles(db: &PgPool) -> Result<usize, sqlx::Error> {
+    let ids = sqlx::query_scalar::<_, i32>("SELECT id FROM articles")
+        .fetch_all(db)
+        .await?;
+
+    let mut reindexed = 0;
+    for id in &ids {
+        // An unreadable file keeps what is indexed, it is not replaced with an empty body
+        let md_contents = match read_article_markdown(*id) {
+            Ok(md_contents) => md_contents,
+            Err(e) => {
+                log_with_colors("WARN", &format!("Failed to read markdown of article {}, keeping its index: {}", id, e));
+                continue;
+            }
+        };
+
+        sqlx::query("UPDATE articles SET search_body = $1 WHERE id = $2")
+            .bind(markdown_to_text(&md_contents))
+            .bind(id)
+            .execute(db)
+            .await?;
+        reindexed += 1;
+    }
+
+    Ok(reindexed)
+}
+
+//#[get("/search")]
+pub async fn search(
+    state: Data<AppState>,
+    search_query: Query<SearchQuery>,
+    list_query: Query<ListQuery>,
+) -> HttpResponse {
+    let query = search_query.q.as_deref().unwrap_or("").trim().to_string();
+    if query.is_empty() {
+        log_with_colors("WARN", "GET 400 /search");
+        return HttpResponse::BadRequest().body("Missing search query q");
+    }
+
+    let listing = match ArticleListing::from_query(&list_query) {
+        Ok(listing) => listing,
+        Err(e) => {
+            log_with_colors("WARN", "GET 400 /search");
+            return HttpResponse::BadRequest().body(e);
+        }
+    };
+    let limit = search_query.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+    let offset = search_query.offset.unwrap_or(0).max(0);
+
+    match search_articles(&state.db, &query, &listing, limit, offset).await {
+        Ok(results) => {
+            let total = results.first().map_or(0, |result| result.total);
+
+            log_with_colors("INFO", "GET 200 /search");
+            HttpResponse::Ok().json(SearchResponse { query, total, results })
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Search failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to search articles")
+        }
+    }
+}
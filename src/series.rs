@@ -0,0 +1,373 @@
+use actix_web::http::header::VARY;
+use actix_web::web::{Data, Json, Path, Query, ReqData};
+use actix_web::{HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use crate::entities::{ArticleEntity, Claims, SeriesArticlesRequest, SeriesCount, SeriesEntity, SeriesInfo, SeriesPart, SeriesRequest, SeriesResponse};
+use crate::enums::ArticleStatus;
+use crate::i18n::{requested_languages, LanguageQuery};
+use crate::review::require_editor;
+use crate::services::localize_articles;
+use crate::slugs::{slug_from_title, validate_slug};
+use crate::utils::log_with_colors;
+use crate::AppState;
+
+async fn find_series(db: &PgPool, slug: &str) -> Result<Option<SeriesEntity>, sqlx::Error> {
+    sqlx::query_as::<_, SeriesEntity>("SELECT * FROM series WHERE slug = $1")
+        .bind(slug)
+        .fetch_optional(db)
+        .await
+}
+
+// Series title, position and neighbours of an article, None when it is not part of one
+pub async fn load_series_info(db: &PgPool, article_id: i32) -> Result<Option<SeriesInfo>, sqlx::Error> {
+    let series = sqlx::query_as::<_, SeriesEntity>(
+        "SELECT series.* FROM series JOIN series_articles ON series_articles.series_id = series.id WHERE series_articles.article_id = $1"
+    )
+        .bind(article_id)
+        .fetch_optional(db)
+        .await?;
+    let Some(series) = series else {
+        return Ok(None);
+    };
+
+    let parts = sqlx::query_as::<_, SeriesPart>(
+        r#"
+        SELECT articles.id, articles.slug, articles.title
+        FROM series_articles
+        JOIN articles ON articles.id = series_articles.article_id
+        WHERE series_articles.series_id = $1 AND articles.status = $2
+        ORDER BY series_articles.position
+        "#
+    )
+        .bind(series.id)
+        .bind::<i32>(ArticleStatus::Published.into())
+        .fetch_all(db)
+        .await?;
+
+    // Positions are counted rather than read, so deleted and unpublished parts leave no gap
+    let Some(index) = parts.iter().position(|part| part.id == article_id) else {
+        return Ok(None);
+    };
+    Ok(Some(SeriesInfo {
+        id: series.id,
+        slug: series.slug,
+        title: series.title,
+        position: index + 1,
+        total: parts.len(),
+        previous: index.checked_sub(1).and_then(|previous| parts.get(previous)).cloned(),
+        next: parts.get(index + 1).cloned(),
+    }))
+}
+
+async fn load_series_response(db: &PgPool, series: SeriesEntity, languages: &[String]) -> Result<SeriesResponse, sqlx::Error> {
+    let articles = sqlx::query_as::<_, ArticleEntity>(
+        r#"
+        SELECT articles.*
+        FROM series_articles
+        JOIN articles ON articles.id = series_articles.article_id
+        WHERE series_articles.series_id = $1 AND articles.status = $2
+        ORDER BY series_articles.position
+        "#
+    )
+        .bind(series.id)
+        .bind::<i32>(ArticleStatus::Published.into())
+        .fetch_all(db)
+        .await?;
+
+    Ok(SeriesResponse { series, articles: localize_articles(db, articles, languages).await? })
+}
+
+//#[get("/series")]
+pub async fn fetch_series_list(state: Data<AppState>) -> HttpResponse {
+    match sqlx::query_as::<_, SeriesCount>(
+        r#"
+        SELECT series.*, COUNT(articles.id) AS article_count
+        FROM series
+        LEFT JOIN series_articles ON series_articles.series_id = series.id
+        LEFT JOIN articles ON articles.id = series_articles.article_id AND articles.status = $1
+        GROUP BY series.id
+        ORDER BY series.title
+        "#
+    )
+        .bind::<i32>(ArticleStatus::Published.into())
+        .fetch_all(&state.db)
+        .await
+    {
+        Ok(series) => {
+            log_with_colors("INFO", "GET 200 /series");
+            HttpResponse::Ok().json(series)
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to load series")
+        }
+    }
+}
+
+//#[get("/series/{slug}")]
+pub async fn fetch_series(
+    req: HttpRequest,
+    state: Data<AppState>,
+    slug: Path<String>,
+    query: Query<LanguageQuery>,
+) -> HttpResponse {
+    let languages = requested_languages(&req, &query);
+
+    let series = match find_series(&state.db, &slug).await {
+        Ok(Some(series)) => series,
+        Ok(None) => {
+            log_with_colors("WARN", "GET 404 /series/{slug}");
+            return HttpResponse::NotFound().body("Series not found");
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            return HttpResponse::InternalServerError().body("Failed to load series");
+        }
+    };
+
+    match load_series_response(&state.db, series, &languages).await {
+        Ok(response) => {
+            log_with_colors("INFO", "GET 200 /series/{slug}");
+            HttpResponse::Ok().insert_header((VARY, "Accept-Language")).json(response)
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to load series")
+        }
+    }
+}
+
+fn respond_with_saved_series(result: Result<Option<SeriesEntity>, sqlx::Error>, method: &str, path: &str) -> HttpResponse {
+    match result {
+        Ok(Some(series)) if method == "POST" => {
+            log_with_colors("INFO", &format!("{} 201 {}", method, path));
+            HttpResponse::Created().json(series)
+        }
+        Ok(Some(series)) => {
+            log_with_colors("INFO", &format!("{} 200 {}", method, path));
+            HttpResponse::Ok().json(series)
+        }
+        Ok(None) => {
+            log_with_colors("WARN", &format!("{} 404 {}", method, path));
+            HttpResponse::NotFound().body("Series not found")
+        }
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            log_with_colors("WARN", &format!("{} 409 {}", method, path));
+            HttpResponse::Conflict().body("Another series already uses this slug")
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Failed to save series: {}", e));
+            HttpResponse::InternalServerError().body("Failed to save series")
+        }
+    }
+}
+
+fn validate_series(request: &SeriesRequest) -> Result<(), String> {
+    if request.title.trim().is_empty() {
+        return Err("A series needs a title".to_string());
+    }
+    match &request.slug {
+        Some(slug) => validate_slug(slug),
+        None => Ok(()),
+    }
+}
+
+//#[post("/series")]
+pub async fn create_series(state: Data<AppState>, claims: ReqData<Claims>, request: Json<SeriesRequest>) -> HttpResponse {
+    if let Err(response) = require_editor(&state.db, &claims.sub, "POST /series").await {
+        return response;
+    }
+    if let Err(message) = validate_series(&request) {
+        log_with_colors("WARN", "POST 400 /series");
+        return HttpResponse::BadRequest().body(message);
+    }
+
+    let slug = request.slug.clone().unwrap_or_else(|| slug_from_title(&request.title));
+    let created = sqlx::query_as::<_, SeriesEntity>(
+        "INSERT INTO series (slug, title, description) VALUES ($1, $2, $3) RETURNING *"
+    )
+        .bind(&slug)
+        .bind(request.title.trim())
+        .bind(&request.description)
+        .fetch_one(&state.db)
+        .await
+        .map(Some);
+
+    respond_with_saved_series(created, "POST", "/series")
+}
+
+//#[put("/series/{slug}")]
+pub async fn update_series(state: Data<AppState>, claims: ReqData<Claims>, slug: Path<String>, request: Json<SeriesRequest>) -> HttpResponse {
+    if let Err(response) = require_editor(&state.db, &claims.sub, "PUT /series/{slug}").await {
+        return response;
+    }
+    if let Err(message) = validate_series(&request) {
+        log_with_colors("WARN", "PUT 400 /series/{slug}");
+        return HttpResponse::BadRequest().body(message);
+    }
+
+    let updated = sqlx::query_as::<_, SeriesEntity>(
+        r#"
+        WITH updated AS (
+            UPDATE series SET slug = COALESCE($1, slug), title = $2, description = $3 WHERE slug = $4 RETURNING *
+        ), bumped AS (
+            -- The series is part of every one of its articles, their ETags change with it
+            UPDATE articles SET version = version + 1
+            WHERE id IN (SELECT article_id FROM series_articles WHERE series_id IN (SELECT id FROM updated))
+        )
+        SELECT * FROM updated
+        "#
+    )
+        .bind(&request.slug)
+        .bind(request.title.trim())
+        .bind(&request.description)
+        .bind(slug.as_str())
+        .fetch_optional(&state.db)
+        .await;
+
+    respond_with_saved_series(updated, "PUT", "/series/{slug}")
+}
+
+// The articles stay, they only leave the series
+//#[delete("/series/{slug}")]
+pub async fn delete_series(state: Data<AppState>, claims: ReqData<Claims>, slug: Path<String>) -> HttpResponse {
+    if let Err(response) = require_editor(&state.db, &claims.sub, "DELETE /series/{slug}").await {
+        return response;
+    }
+    // The parts are read before the delete takes them along
+    match sqlx::query_scalar::<_, i64>(
+        r#"
+        WITH deleted AS (
+            DELETE FROM series WHERE slug = $1 RETURNING id
+        ), bumped AS (
+            UPDATE articles SET version = version + 1
+            WHERE id IN (SELECT article_id FROM series_articles WHERE series_id IN (SELECT id FROM deleted))
+        )
+        SELECT COUNT(*) FROM deleted
+        "#
+    )
+        .bind(slug.as_str())
+        .fetch_one(&state.db)
+        .await
+    {
+        Ok(deleted) if deleted > 0 => {
+            log_with_colors("INFO", "DELETE 200 /series/{slug}");
+            HttpResponse::Ok().body("Series deleted successfully")
+        }
+        Ok(_) => {
+            log_with_colors("WARN", "DELETE 404 /series/{slug}");
+            HttpResponse::NotFound().body("Series not found")
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Failed to delete series: {}", e));
+            HttpResponse::InternalServerError().body("Failed to delete series")
+        }
+    }
+}
+
+// Replaces the parts of a series, in the order given
+async fn set_series_articles(db: &PgPool, series_id: i32, article_ids: &[i32]) -> Result<(), sqlx::Error> {
+    let mut transaction = db.begin().await?;
+
+    // Articles leaving the series and those in it after the change, numbering included, get a new version
+    sqlx::query(
+        r#"
+        WITH removed AS (
+            DELETE FROM series_articles WHERE series_id = $1 RETURNING article_id
+        )
+        UPDATE articles SET version = version + 1
+        WHERE id IN (SELECT article_id FROM removed) OR id = ANY($2)
+        "#
+    )
+        .bind(series_id)
+        .bind(article_ids)
+        .execute(&mut *transaction)
+        .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO series_articles (series_id, article_id, position)
+        SELECT $1, article_id, position FROM UNNEST($2::INTEGER[]) WITH ORDINALITY AS parts (article_id, position)
+        "#
+    )
+        .bind(series_id)
+        .bind(article_ids)
+        .execute(&mut *transaction)
+        .await?;
+
+    transaction.commit().await
+}
+
+//#[put("/series/{slug}/articles")]
+pub async fn set_series_parts(
+    req: HttpRequest,
+    state: Data<AppState>,
+    claims: ReqData<Claims>,
+    slug: Path<String>,
+    request: Json<SeriesArticlesRequest>,
+) -> HttpResponse {
+    if let Err(response) = require_editor(&state.db, &claims.sub, "PUT /series/{slug}/articles").await {
+        return response;
+    }
+    let article_ids = &request.articles;
+    if article_ids.iter().enumerate().any(|(index, id)| article_ids[..index].contains(id)) {
+        log_with_colors("WARN", "PUT 400 /series/{slug}/articles");
+        return HttpResponse::BadRequest().body("An article can only appear once in a series");
+    }
+
+    let series = match find_series(&state.db, &slug).await {
+        Ok(Some(series)) => series,
+        Ok(None) => {
+            log_with_colors("WARN", "PUT 404 /series/{slug}/articles");
+            return HttpResponse::NotFound().body("Series not found");
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            return HttpResponse::InternalServerError().body("Failed to load series");
+        }
+    };
+
+    // Moving an article between series is explicit, it has to leave the other one first
+    let elsewhere = sqlx::query_scalar::<_, i32>(
+        "SELECT article_id FROM series_articles WHERE article_id = ANY($1) AND series_id <> $2 ORDER BY article_id"
+    )
+        .bind(article_ids)
+        .bind(series.id)
+        .fetch_all(&state.db)
+        .await;
+    match elsewhere {
+        Ok(ids) if ids.is_empty() => {}
+        Ok(ids) => {
+            log_with_colors("WARN", "PUT 409 /series/{slug}/articles");
+            return HttpResponse::Conflict().body(format!("Articles {:?} already belong to another series", ids));
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            return HttpResponse::InternalServerError().body("Failed to load series");
+        }
+    }
+
+    match set_series_articles(&state.db, series.id, article_ids).await {
+        Ok(()) => {}
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+            log_with_colors("WARN", "PUT 400 /series/{slug}/articles");
+            return HttpResponse::BadRequest().body("Unknown article id");
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Failed to set series articles: {}", e));
+            return HttpResponse::InternalServerError().body("Failed to set series articles");
+        }
+    }
+
+    let languages = requested_languages(&req, &LanguageQuery { lang: None });
+    match load_series_response(&state.db, series, &languages).await {
+        Ok(response) => {
+            log_with_colors("INFO", "PUT 200 /series/{slug}/articles");
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to load series")
+        }
+    }
+}
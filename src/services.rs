@@ -1,95 +1,771 @@
 use std::fs::{create_dir_all, File};
 use std::io::Write;
 use actix_multipart::Multipart;
-use actix_web::{get, post, put, delete, web::{Data, Json, Path}, Responder, HttpResponse, Error};
+use actix_web::{web::{Data, Json, Path, Query, ReqData}, Responder, HttpRequest, HttpResponse, Error};
+use actix_web::http::header::{CACHE_CONTROL, CONTENT_LANGUAGE, ETAG, IF_MATCH, LINK, VARY};
 use bcrypt::{hash, verify, DEFAULT_COST};
-use sqlx::{self, Row};
+use chrono::{DateTime, Utc};
+use sqlx::{self, PgPool, Postgres, QueryBuilder, Row};
 use crate::{entities, utils, AppState};
-use utils::{log_with_colors, read_file_contents, read_photo_as_base64};
-use entities::{ArticleEntity, ArticleCreateRequest, ArticleResponse, LoginRequest, User};
+use utils::{article_dir, article_etag, article_file_path, parse_article_etag, article_md_path, article_photo_path, log_with_colors, markdown_to_text, read_article_markdown, read_file_contents, read_photo_as_base64};
+use entities::{ArticleEntity, ArticleCreateRequest, Claims, ArticleListItem, ArticleResponse, ArticleTranslationEntity, ArticleUpdateRequest, ArticleWriteResponse, LintWarning, LoginRequest, TranslationRequest, User};
 use futures_util::stream::StreamExt;
 use serde_json;
 use crate::entities::SignupRequest;
 use crate::utils::generate_jwt;
+use crate::enums::{ArticleStatus, ArticleType, UserRole};
+use crate::lint::lint_article;
+use crate::listing::{ArticleListing, ListQuery, CURRENTLY_PINNED};
+use crate::pagination::{Page, PageQuery, PageRequest};
+use crate::i18n::{default_language, negotiate, normalize_language, requested_languages, LanguageQuery};
+use crate::previews::{preview_allows, PreviewTokenQuery};
+use crate::publishing::resolve_status;
+use crate::related::{load_related, refresh_related};
+use crate::series::load_series_info;
+use crate::revisions::{record_initial_revision, record_revision};
+use crate::review::{check_contributor_edit, check_contributor_status, load_user_role, require_editor};
+use crate::slugs::{self, SlugLookup};
+use crate::trash::trash_article;
+use crate::tags::{load_article_tags, parse_tag_names, set_article_tags, tags_of};
 
-//#[get("/articles")]
-pub async fn fetch_all_articles(state: Data<AppState>) -> impl Responder {
+// ARTICLE LOADERS
+// Shared by the JSON handlers below and the HTML site
 
-    //"GET /articles".to_string()
+// Only published articles, like every loader the public routes use, newest first
+pub async fn load_articles(db: &PgPool) -> Result<Vec<ArticleEntity>, sqlx::Error> {
+    sqlx::query_as::<_, ArticleEntity>(
+        "SELECT * FROM articles WHERE status = $1 ORDER BY published_at DESC NULLS LAST, id DESC"
+    )
+        .bind::<i32>(ArticleStatus::Published.into())
+        .fetch_all(db)
+        .await
+}
 
-    match sqlx::query_as::<_, ArticleEntity>(
-        "SELECT * FROM articles"
+pub async fn load_articles_by_type(db: &PgPool, article_type: i32) -> Result<Vec<ArticleEntity>, sqlx::Error> {
+    sqlx::query_as::<_, ArticleEntity>(
+        "SELECT * FROM articles WHERE article_type = $1 AND status = $2 ORDER BY published_at DESC NULLS LAST, id DESC"
     )
-        .fetch_all(&state.db)
+        .bind(article_type)
+        .bind::<i32>(ArticleStatus::Published.into())
+        .fetch_all(db)
         .await
-    {
+}
+
+pub async fn load_translations(db: &PgPool, article_ids: &[i32]) -> Result<Vec<ArticleTranslationEntity>, sqlx::Error> {
+    sqlx::query_as::<_, ArticleTranslationEntity>(
+        "SELECT * FROM article_translations WHERE article_id = ANY($1) ORDER BY language"
+    )
+        .bind(article_ids)
+        .fetch_all(db)
+        .await
+}
+
+// Swap in the translation that best matches `languages` (most preferred first), falling back
+// to the original. Returns every language the article is available in and whether it was translated.
+fn localize(article: &mut ArticleEntity, translations: &[ArticleTranslationEntity], languages: &[String]) -> (Vec<String>, bool) {
+    let translations: Vec<&ArticleTranslationEntity> = translations
+        .iter()
+        .filter(|translation| translation.article_id == article.id)
+        .collect();
+
+    let mut available = vec![article.language.clone()];
+    available.extend(translations.iter().map(|translation| translation.language.clone()));
+
+    let chosen = negotiate(languages, &available);
+    match translations.iter().find(|translation| Some(&translation.language) == chosen.as_ref()) {
+        Some(translation) => {
+            article.title = translation.title.clone();
+            article.description = translation.description.clone();
+            article.md_filename = translation.md_filename.clone();
+            article.language = translation.language.clone();
+            (available, true)
+        }
+        None => (available, false),
+    }
+}
+
+pub async fn load_article(db: &PgPool, id: i32, languages: &[String]) -> Result<ArticleResponse, sqlx::Error> {
+    let mut article = sqlx::query_as::<_, ArticleEntity>(
+        "SELECT * FROM articles WHERE id = $1"
+    )
+        .bind(id)
+        .fetch_one(db)
+        .await?;
+
+    let translations = load_translations(db, &[id]).await?;
+    let (available_languages, translated) = localize(&mut article, &translations, languages);
+    let tags = load_article_tags(db, &[id]).await?;
+    let related = load_related(db, id).await?;
+    let series = load_series_info(db, id).await?;
+
+    // Construct the file path for the photo
+    let photo_file_path = article_photo_path(article.id);
+
+    // Convert PathBuf to &str
+    let photo_file_path_str = photo_file_path.to_str().expect("Failed to convert photo file path to string");
+
+    // Read the markdown file contents
+    let md_contents = if translated {
+        let md_file_path = article_file_path(article.id, &article.md_filename);
+        read_file_contents(md_file_path.to_str().expect("Failed to convert markdown file path to string"))
+    } else {
+        read_article_markdown(article.id)
+    };
+    let md_contents = md_contents.unwrap_or_else(|e| {
+        log_with_colors("ERROR", &format!("Failed to read markdown file: {}", e));
+        String::new() // Return an empty string or handle error as needed
+    });
+
+    // Read the photo file contents (as base64 string for JSON response)
+    let photo_contents = read_photo_as_base64(&photo_file_path_str).unwrap_or_else(|e| {
+        log_with_colors("ERROR", &format!("Failed to read photo file: {}", e));
+        String::new() // Return an empty string or handle error as needed
+    });
+
+    // Create a response struct to include article data and file contents
+    Ok(ArticleResponse {
+        article,
+        md_contents,
+        photo_contents,
+        available_languages,
+        tags: tags_of(id, &tags),
+        related,
+        series,
+    })
+}
+
+// One page of the listing. Keyset pagination on the sort key plus the unique id
+// stays stable while articles are added or removed, see listing.rs.
+pub async fn load_article_page(db: &PgPool, listing: &ArticleListing, page: &PageRequest) -> Result<Page<ArticleEntity>, sqlx::Error> {
+    let backwards = page.cursor.as_ref().is_some_and(|cursor| cursor.backwards);
+
+    let mut builder = QueryBuilder::<Postgres>::new("SELECT * FROM articles WHERE TRUE");
+    listing.push_filters(&mut builder);
+    if listing.pinned_first {
+        builder.push(format!(" AND NOT ({})", CURRENTLY_PINNED));
+    }
+    if let Some(cursor) = &page.cursor {
+        listing.push_keyset(&mut builder, cursor);
+    }
+    listing.push_order(&mut builder, backwards);
+    // One extra row tells whether there is more to page through
+    builder.push(" LIMIT ").push_bind(page.limit + 1);
+
+    let mut articles = builder.build_query_as::<ArticleEntity>().fetch_all(db).await?;
+
+    let has_more = articles.len() as i64 > page.limit;
+    articles.truncate(page.limit as usize);
+    if backwards {
+        articles.reverse();
+    }
+
+    // Walking forwards there is a previous page whenever we started from a cursor,
+    // walking backwards there is always a next page (the one we came from)
+    let (has_next, has_prev) = if backwards {
+        (true, has_more)
+    } else {
+        (has_more, page.cursor.is_some())
+    };
+
+    let next = articles.last().filter(|_| has_next).map(|article| listing.cursor(article, false));
+    let prev = articles.first().filter(|_| has_prev).map(|article| listing.cursor(article, true));
+
+    // Pinned articles sit outside the keyset order and lead the first page, on top of its limit
+    if listing.pinned_first && !has_prev {
+        let mut pinned = QueryBuilder::<Postgres>::new(format!("SELECT * FROM articles WHERE {}", CURRENTLY_PINNED));
+        listing.push_filters(&mut pinned);
+        pinned.push(" ORDER BY pinned_position, id");
+        let pinned = pinned.build_query_as::<ArticleEntity>().fetch_all(db).await?;
+        articles.splice(0..0, pinned);
+    }
+
+    let total = if page.count {
+        let mut count = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM articles WHERE TRUE");
+        listing.push_filters(&mut count);
+        Some(count.build_query_scalar::<i64>().fetch_one(db).await?)
+    } else {
+        None
+    };
+
+    Ok(Page {
+        items: articles,
+        next,
+        prev,
+        total,
+    })
+}
+
+// Translations and tags of articles already loaded, in the languages the client asked for
+pub async fn localize_articles(db: &PgPool, articles: Vec<ArticleEntity>, languages: &[String]) -> Result<Vec<ArticleListItem>, sqlx::Error> {
+    let ids: Vec<i32> = articles.iter().map(|article| article.id).collect();
+    let translations = load_translations(db, &ids).await?;
+    let tags = load_article_tags(db, &ids).await?;
+
+    Ok(articles.into_iter().map(|mut article| {
+        let (available_languages, _) = localize(&mut article, &translations, languages);
+        let tags = tags_of(article.id, &tags);
+        ArticleListItem { article, available_languages, tags }
+    }).collect())
+}
+
+// A page of articles in the languages the client asked for, see i18n.rs
+pub async fn load_localized_articles(
+    db: &PgPool,
+    listing: &ArticleListing,
+    page: &PageRequest,
+    languages: &[String],
+) -> Result<Page<ArticleListItem>, sqlx::Error> {
+    let Page { items, next, prev, total } = load_article_page(db, listing, page).await?;
+    Ok(Page { items: localize_articles(db, items, languages).await?, next, prev, total })
+}
+
+// A single article in the languages the client asked for, without its files
+pub async fn load_localized_article(db: &PgPool, id: i32, languages: &[String]) -> Result<Option<ArticleListItem>, sqlx::Error> {
+    let article = sqlx::query_as::<_, ArticleEntity>(
+        "SELECT * FROM articles WHERE id = $1"
+    )
+        .bind(id)
+        .fetch_optional(db)
+        .await?;
+    let Some(mut article) = article else {
+        return Ok(None);
+    };
+
+    let translations = load_translations(db, &[id]).await?;
+    let (available_languages, _) = localize(&mut article, &translations, languages);
+    let tags = load_article_tags(db, &[id]).await?;
+    Ok(Some(ArticleListItem { article, available_languages, tags: tags_of(id, &tags) }))
+}
+
+// Responds with a page of the listing, shared by GET /articles and GET /tags/{slug}/articles
+pub async fn respond_with_listing(
+    req: &HttpRequest,
+    db: &PgPool,
+    languages: &[String],
+    listing: Result<ArticleListing, String>,
+    page_query: &PageQuery,
+    route: &str,
+) -> HttpResponse {
+    let page = PageRequest::from_query(page_query);
+    let (listing, page) = match (listing, page) {
+        (Ok(listing), Ok(page)) if page.cursor.as_ref().is_none_or(|cursor| listing.accepts(cursor)) => (listing, page),
+        (Err(message), _) | (_, Err(message)) => {
+            log_with_colors("WARN", &format!("GET 400 {}", route));
+            return HttpResponse::BadRequest().body(message);
+        }
+        _ => {
+            log_with_colors("WARN", &format!("GET 400 {} - Cursor of another ordering", route));
+            return HttpResponse::BadRequest().body("The cursor belongs to a different sort order");
+        }
+    };
+
+    match load_localized_articles(db, &listing, &page, languages).await {
         Ok(articles) => {
-            log_with_colors("INFO", "GET 200 /articles");
-            HttpResponse::Ok().json(articles)
+            log_with_colors("INFO", &format!("GET 200 {}", route));
+            let mut response = HttpResponse::Ok();
+            response.insert_header((VARY, "Accept-Language"));
+            if let Some(link) = articles.link_header(req) {
+                response.insert_header((LINK, link));
+            }
+            if let Some(total) = articles.total {
+                response.insert_header(("X-Total-Count", total.to_string()));
+            }
+            response.json(articles.items)
         }
         Err(e) => {
             log_with_colors("ERROR", &format!("Database query failed: {}", e));
-            log_with_colors("WARN", "GET 404 /articles");
+            log_with_colors("WARN", &format!("GET 404 {}", route));
             HttpResponse::NotFound().body("No Articles found")
         }
     }
 }
 
-//#[get("/articles/{id}")]
+//#[get("/articles")]
+pub async fn fetch_all_articles(
+    req: HttpRequest,
+    state: Data<AppState>,
+    query: Query<LanguageQuery>,
+    page_query: Query<PageQuery>,
+    list_query: Query<ListQuery>,
+) -> impl Responder {
+
+    //"GET /articles".to_string()
+
+    let languages = requested_languages(&req, &query);
+    respond_with_listing(&req, &state.db, &languages, ArticleListing::from_query(&list_query), &page_query, "/articles").await
+}
+
+// Articles live at their slug, numeric ids and old slugs redirect there
+//#[get("/articles/{slug}")]
 pub async fn fetch_article(
+    req: HttpRequest,
     state: Data<AppState>,
-    id: Path<i32>,
+    key: Path<String>,
+    query: Query<LanguageQuery>,
+    preview_query: Query<PreviewTokenQuery>,
 ) -> impl Responder {
+    let id = match slugs::lookup(&state.db, &key).await {
+        Ok(SlugLookup::Current(id)) => id,
+        Ok(SlugLookup::Moved { slug, public: true, .. }) => return slugs::redirect(&req, &format!("/articles/{}", slug)),
+        // The slug of an unpublished article is only given away to a preview link, ?preview= goes along
+        Ok(SlugLookup::Moved { id, slug, public: false }) => {
+            let previewing = match preview_query.preview.as_deref() {
+                Some(token) => preview_allows(&state.db, token, id).await,
+                None => Ok(false),
+            };
+            return match previewing {
+                Ok(true) => slugs::redirect(&req, &format!("/articles/{}", slug)),
+                Ok(false) => {
+                    log_with_colors("WARN", "GET 404 /articles/{slug}");
+                    HttpResponse::NotFound().body("Article not found")
+                }
+                Err(e) => {
+                    log_with_colors("ERROR", &format!("Database query failed: {}", e));
+                    HttpResponse::InternalServerError().body("Failed to load article")
+                }
+            };
+        }
+        Ok(SlugLookup::NotFound) => {
+            log_with_colors("WARN", "GET 404 /articles/{slug}");
+            return HttpResponse::NotFound().body("Article not found");
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            return HttpResponse::InternalServerError().body("Failed to load article");
+        }
+    };
+
     // Fetch the article from the database
-    match sqlx::query_as::<_, ArticleEntity>(
-        "SELECT * FROM articles WHERE id = $1"
-    )
-        .bind(id.into_inner())
+    let response = match load_article(&state.db, id, &requested_languages(&req, &query)).await {
+        Ok(response) => response,
+        Err(_) => {
+            log_with_colors("WARN", "GET 404 /articles/{slug}");
+            return HttpResponse::NotFound().body("Article not found");
+        }
+    };
+    if response.article.is_public() {
+        log_with_colors("INFO", "GET 200 articles/{slug}");
+        return HttpResponse::Ok()
+            .insert_header((CONTENT_LANGUAGE, response.article.language.clone()))
+            .insert_header((VARY, "Accept-Language"))
+            .insert_header((ETAG, article_etag(response.article.version)))
+            .json(response);
+    }
+
+    // An unpublished article is shown to whoever holds one of its preview links, never cached or indexed
+    let previewing = match preview_query.preview.as_deref() {
+        Some(token) => preview_allows(&state.db, token, id).await,
+        None => Ok(false),
+    };
+    match previewing {
+        Ok(true) => {
+            log_with_colors("INFO", "GET 200 articles/{slug} - preview");
+            HttpResponse::Ok()
+                .insert_header((CONTENT_LANGUAGE, response.article.language.clone()))
+                .insert_header((CACHE_CONTROL, "private, no-store"))
+                .insert_header(("X-Robots-Tag", "noindex"))
+                .insert_header((ETAG, article_etag(response.article.version)))
+                .json(response)
+        }
+        Ok(false) => {
+            log_with_colors("WARN", "GET 404 /articles/{slug}");
+            HttpResponse::NotFound().body("Article not found")
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to load article")
+        }
+    }
+}
+
+// Like the article, the photo is public once the article is published
+//#[get("/articles/{id}/photo")]
+pub async fn fetch_article_photo(state: Data<AppState>, id: Path<i32>) -> impl Responder {
+    let id = id.into_inner();
+    match sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM articles WHERE id = $1 AND status = $2)")
+        .bind(id)
+        .bind::<i32>(ArticleStatus::Published.into())
         .fetch_one(&state.db)
         .await
     {
-        Ok(article) => {
-            // Construct file paths for markdown and photo// Get the home directory dynamically
-            let home_dir = home::home_dir().expect("Failed to get home directory");
-            let md_file_path = home_dir.join(format!("hephaestus-blog/articles/{}/{}.md", article.id, article.id));
-            let photo_file_path = home_dir.join(format!("hephaestus-blog/articles/{}/{}.jpg", article.id, article.id));
-
-            // Convert PathBuf to &str
-            let md_file_path_str = md_file_path.to_str().expect("Failed to convert markdown file path to string");
-            let photo_file_path_str = photo_file_path.to_str().expect("Failed to convert photo file path to string");
-
-            // Read the markdown file contents
-            let md_contents = read_file_contents(&md_file_path_str).unwrap_or_else(|e| {
-                log_with_colors("ERROR", &format!("Failed to read markdown file: {}", e));
-                String::new() // Return an empty string or handle error as needed
-            });
-
-            // Read the photo file contents (as base64 string for JSON response)
-            let photo_contents = read_photo_as_base64(&photo_file_path_str).unwrap_or_else(|e| {
-                log_with_colors("ERROR", &format!("Failed to read photo file: {}", e));
-                String::new() // Return an empty string or handle error as needed
-            });
-
-            // Create a response struct to include article data and file contents
-            let response = ArticleResponse {
-                article,
-                md_contents,
-                photo_contents,
-            };
+        Ok(true) => {}
+        Ok(false) => {
+            log_with_colors("WARN", "GET 404 /articles/{id}/photo");
+            return HttpResponse::NotFound().body("Photo not found");
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            return HttpResponse::InternalServerError().body("Failed to load photo");
+        }
+    }
 
-            log_with_colors("INFO", "GET 200 articles/{id}");
-            HttpResponse::Ok().json(response)
+    match std::fs::read(article_photo_path(id)) {
+        Ok(photo) => {
+            log_with_colors("INFO", "GET 200 /articles/{id}/photo");
+            HttpResponse::Ok().content_type("image/jpeg").body(photo)
         }
         Err(_) => {
-            log_with_colors("WARN", "GET 404 /articles/{id}");
-            HttpResponse::NotFound().body("Article not found")
+            log_with_colors("WARN", "GET 404 /articles/{id}/photo");
+            HttpResponse::NotFound().body("Photo not found")
+        }
+    }
+}
+
+// ARTICLE WRITERS
+// Shared by the REST handlers below and the GraphQL mutations
+
+// Why an article could not be stored, the REST handlers turn it into a status code
+pub enum ArticleWriteError {
+    Invalid(String),
+    NotFound,
+    SlugTaken(String),
+    Forbidden(String),
+    Conflict(String), // the article is not in a status that allows the change
+    VersionMismatch(i32), // If-Match named another version, this one is current
+    VersionMissing, // an update or delete without If-Match
+    Lint(Vec<LintWarning>),
+    Database(sqlx::Error),
+    Storage(String),
+}
+
+impl From<sqlx::Error> for ArticleWriteError {
+    fn from(e: sqlx::Error) -> Self {
+        ArticleWriteError::Database(e)
+    }
+}
+
+impl ArticleWriteError {
+    pub fn message(&self) -> String {
+        match self {
+            ArticleWriteError::Invalid(message) | ArticleWriteError::Forbidden(message) | ArticleWriteError::Conflict(message) => message.clone(),
+            ArticleWriteError::NotFound => "Article not found".to_string(),
+            ArticleWriteError::SlugTaken(slug) => format!("Slug {} is already in use", slug),
+            ArticleWriteError::VersionMismatch(_) => "The article was changed meanwhile, load it again".to_string(),
+            ArticleWriteError::VersionMissing => "Send the ETag of the article in If-Match".to_string(),
+            ArticleWriteError::Lint(_) => "Markdown lint failed".to_string(),
+            ArticleWriteError::Database(_) | ArticleWriteError::Storage(_) => "Failed to store article".to_string(),
+        }
+    }
+
+    // `route` such as "POST /articles", for the log line
+    pub fn response(&self, route: &str) -> HttpResponse {
+        let (method, path) = route.split_once(' ').unwrap_or(("", route));
+        match self {
+            ArticleWriteError::Invalid(message) => {
+                log_with_colors("WARN", &format!("{} 400 {} - {}", method, path, message));
+                HttpResponse::BadRequest().body(message.clone())
+            }
+            ArticleWriteError::NotFound => {
+                log_with_colors("WARN", &format!("{} 404 {}", method, path));
+                HttpResponse::NotFound().body(self.message())
+            }
+            ArticleWriteError::SlugTaken(_) => {
+                log_with_colors("WARN", &format!("{} 409 {} - Slug taken", method, path));
+                HttpResponse::Conflict().body(self.message())
+            }
+            ArticleWriteError::Forbidden(message) => {
+                log_with_colors("WARN", &format!("{} 403 {} - {}", method, path, message));
+                HttpResponse::Forbidden().body(message.clone())
+            }
+            ArticleWriteError::Conflict(message) => {
+                log_with_colors("WARN", &format!("{} 409 {} - {}", method, path, message));
+                HttpResponse::Conflict().body(message.clone())
+            }
+            ArticleWriteError::VersionMismatch(version) => {
+                log_with_colors("WARN", &format!("{} 412 {} - Version {} is current", method, path, version));
+                HttpResponse::PreconditionFailed()
+                    .insert_header((ETAG, article_etag(*version)))
+                    .json(serde_json::json!({ "error": self.message(), "version": version }))
+            }
+            ArticleWriteError::VersionMissing => {
+                log_with_colors("WARN", &format!("{} 428 {} - If-Match missing", method, path));
+                HttpResponse::PreconditionRequired().body(self.message())
+            }
+            ArticleWriteError::Lint(warnings) => {
+                log_with_colors("WARN", &format!("{} 422 {} - Markdown lint failed", method, path));
+                HttpResponse::UnprocessableEntity().json(serde_json::json!({ "error": self.message(), "warnings": warnings }))
+            }
+            ArticleWriteError::Database(e) => {
+                log_with_colors("ERROR", &format!("{} 500 {} - Database query failed: {}", method, path, e));
+                HttpResponse::InternalServerError().body(self.message())
+            }
+            ArticleWriteError::Storage(e) => {
+                log_with_colors("ERROR", &format!("{} 500 {} - {}", method, path, e));
+                HttpResponse::InternalServerError().body(self.message())
+            }
         }
     }
 }
 
+// Related articles are a cache, failing to refresh them does not fail the write
+pub async fn refresh_related_or_log(db: &PgPool, id: i32) {
+    if let Err(e) = refresh_related(db, id).await {
+        log_with_colors("ERROR", &format!("Failed to refresh related articles of {}: {}", id, e));
+    }
+}
+
+// Why a write that checked the version changed nothing: the article is gone or has another version
+pub async fn version_mismatch(db: &PgPool, id: i32) -> ArticleWriteError {
+    match sqlx::query_scalar::<_, i32>("SELECT version FROM articles WHERE id = $1 AND status <> $2")
+        .bind(id)
+        .bind::<i32>(ArticleStatus::Trashed.into())
+        .fetch_optional(db)
+        .await
+    {
+        Ok(Some(version)) => ArticleWriteError::VersionMismatch(version),
+        Ok(None) => ArticleWriteError::NotFound,
+        Err(e) => ArticleWriteError::Database(e),
+    }
+}
+
+// The version If-Match asks an update or delete for, None for "*". Without the header the write
+// is refused, a client that leaves it out would overwrite changes it has not seen.
+pub fn required_version(req: &HttpRequest) -> Result<Option<i32>, ArticleWriteError> {
+    let value = req.headers().get(IF_MATCH).ok_or(ArticleWriteError::VersionMissing)?;
+    match value.to_str().map(str::trim) {
+        Ok("*") => Ok(None),
+        Ok(tag) => parse_article_etag(tag)
+            .map(Some)
+            .ok_or_else(|| ArticleWriteError::Invalid("If-Match is not an ETag of an article".to_string())),
+        Err(_) => Err(ArticleWriteError::Invalid("If-Match is not an ETag of an article".to_string())),
+    }
+}
+
+// A slug chosen by the editor has to be valid and free, otherwise one is made from the title
+async fn choose_slug(db: &PgPool, requested: Option<&str>, title: &str, article_id: Option<i32>) -> Result<String, ArticleWriteError> {
+    match requested {
+        Some(slug) => {
+            slugs::validate_slug(slug).map_err(ArticleWriteError::Invalid)?;
+            if slugs::slug_taken(db, slug, article_id).await? {
+                return Err(ArticleWriteError::SlugTaken(slug.to_string()));
+            }
+            Ok(slug.to_string())
+        }
+        None => Ok(slugs::unique_slug(db, &slugs::slug_from_title(title), article_id).await?),
+    }
+}
+
+fn write_upload(path: &std::path::Path, contents: &[u8]) -> Result<(), ArticleWriteError> {
+    let mut file = File::create(path).map_err(|e| ArticleWriteError::Storage(format!("Failed to create {}: {}", path.display(), e)))?;
+    file.write_all(contents).map_err(|e| ArticleWriteError::Storage(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+// Stores a new article with its markdown and photo, `author_email` is the JWT subject
+pub async fn save_new_article(
+    db: &PgPool,
+    new_article: &ArticleCreateRequest,
+    markdown: Option<&str>,
+    photo: Option<&[u8]>,
+    author_email: &str,
+) -> Result<ArticleWriteResponse, ArticleWriteError> {
+    ArticleType::try_from(new_article.article_type).map_err(ArticleWriteError::Invalid)?;
+
+    // Lint the title and markdown before anything is stored
+    let warnings = lint_article(&new_article.title, markdown.unwrap_or(""));
+    if warnings.iter().any(|warning| warning.blocking) {
+        return Err(ArticleWriteError::Lint(warnings));
+    }
+
+    let language = match &new_article.language {
+        Some(language) => normalize_language(language).ok_or_else(|| ArticleWriteError::Invalid("Invalid language".to_string()))?,
+        None => default_language(),
+    };
+    let tag_names = parse_tag_names(&new_article.tags).map_err(ArticleWriteError::Invalid)?;
+
+    // Articles of contributors start as drafts, those of editors are public unless asked otherwise
+    let (author_id, role) = load_user_role(db, author_email).await?;
+    let requested_status = match (role, new_article.status, new_article.publish_at) {
+        (UserRole::Contributor, None, None) => Some(ArticleStatus::Draft.into()),
+        _ => new_article.status,
+    };
+    let (status, publish_at) = resolve_status(requested_status, new_article.publish_at, None).map_err(ArticleWriteError::Invalid)?;
+    if role == UserRole::Contributor {
+        check_contributor_status(status)?;
+    }
+    let slug = choose_slug(db, new_article.slug.as_deref(), &new_article.title, None).await?;
+
+    // Insert the article into the database, created and updated at the same moment, published too unless it waits
+    let record = sqlx::query(
+        r#"
+        INSERT INTO articles (title, description, article_type, language, search_body, slug, author_id, status, publish_at, published_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, CASE WHEN $8 = $10 THEN now() END)
+        RETURNING id, created_at, published_at
+        "#
+    )
+        .bind(&new_article.title)
+        .bind(&new_article.description)
+        .bind(new_article.article_type)
+        .bind(&language)
+        .bind(markdown_to_text(markdown.unwrap_or("")))
+        .bind(&slug)
+        .bind(author_id)
+        .bind::<i32>(status.into())
+        .bind(publish_at)
+        .bind::<i32>(ArticleStatus::Published.into())
+        .fetch_one(db)
+        .await?;
+    let id: i32 = record.try_get("id")?;
+    let created_at: DateTime<Utc> = record.try_get("created_at")?;
+    let published_at: Option<DateTime<Utc>> = record.try_get("published_at")?;
+
+    // The files go to the article's folder, where the update, the search index and fetch_article read them.
+    // Without markdown the article is stored with an empty document, like it is indexed and linted.
+    let article_dir_path = article_dir(id);
+    create_dir_all(&article_dir_path).map_err(|e| ArticleWriteError::Storage(format!("Failed to create {}: {}", article_dir_path.display(), e)))?;
+
+    write_upload(&article_md_path(id), markdown.unwrap_or("").as_bytes())?;
+    if let Some(photo_bytes) = photo {
+        write_upload(&article_photo_path(id), photo_bytes)?;
+    }
+
+    // Create the ArticleEntity instance with the generated ID
+    let article = ArticleEntity {
+        id,
+        title: new_article.title.clone(),
+        description: new_article.description.clone(),
+        md_filename: format!("{}.md", id), // Use the ID for filename
+        photo_filename: format!("{}.jpg", id), // Use the ID for filename
+        article_type: new_article.article_type,
+        language,
+        slug,
+        created_at,
+        updated_at: created_at,
+        published_at,
+        author_id,
+        pinned_position: None,
+        pinned_until: None,
+        status: status.into(),
+        publish_at,
+        version: 1,
+    };
+
+    // Update the article with the markdown and photo filenames
+    sqlx::query(
+        r#"
+        UPDATE articles
+        SET md_filename = $1, photo_filename = $2
+        WHERE id = $3
+        "#
+    )
+        .bind(&article.md_filename)
+        .bind(&article.photo_filename)
+        .bind(id)
+        .execute(db)
+        .await?;
+
+    let tags = set_article_tags(db, id, &tag_names).await?;
+    record_revision(db, id, markdown.unwrap_or(""), author_id).await?;
+    refresh_related_or_log(db, id).await;
+
+    Ok(ArticleWriteResponse { article, tags, warnings })
+}
+
+// Stores new details of an article, the markdown stays as it is. `editor_email` is the JWT subject.
+pub async fn save_article_update(
+    db: &PgPool,
+    id: i32,
+    update: ArticleUpdateRequest,
+    expected_version: Option<i32>,
+    editor_email: &str,
+) -> Result<ArticleWriteResponse, ArticleWriteError> {
+    let ArticleUpdateRequest { article, tags, status, publish_at } = update;
+
+    let tag_names = tags.as_deref().map(parse_tag_names).transpose().map_err(ArticleWriteError::Invalid)?;
+    ArticleType::try_from(article.article_type).map_err(ArticleWriteError::Invalid)?;
+
+    let (current_status, current_publish_at, current_author_id, current_version) =
+        sqlx::query_as::<_, (i32, Option<DateTime<Utc>>, Option<i32>, i32)>(
+            "SELECT status, publish_at, author_id, version FROM articles WHERE id = $1"
+        )
+            .bind(id)
+            .fetch_optional(db)
+            .await?
+            .ok_or(ArticleWriteError::NotFound)?;
+    let current_status = ArticleStatus::from(current_status);
+    if current_status == ArticleStatus::Trashed {
+        return Err(ArticleWriteError::Conflict("The article is in the trash, restore it first".to_string()));
+    }
+    // Checked here to fail before the lint, and again by the UPDATE for a write in between
+    if expected_version.is_some_and(|version| version != current_version) {
+        return Err(ArticleWriteError::VersionMismatch(current_version));
+    }
+    let (status, publish_at) = resolve_status(status, publish_at, Some((current_status, current_publish_at)))
+        .map_err(ArticleWriteError::Invalid)?;
+
+    let (author_id, role) = load_user_role(db, editor_email).await?;
+    if role == UserRole::Contributor {
+        check_contributor_edit(author_id, current_author_id, current_status)?;
+        check_contributor_status(status)?;
+    }
+
+    // An empty slug keeps the current one
+    let requested_slug = match article.slug.as_str() {
+        "" => None,
+        slug => Some(choose_slug(db, Some(slug), &article.title, Some(id)).await?),
+    };
+
+    // The markdown is not part of the update, lint the stored one against the new title.
+    // A file that cannot be read fails the update, it would otherwise pass as an empty document.
+    let md_contents = read_article_markdown(id)
+        .map_err(|e| ArticleWriteError::Storage(format!("Failed to read the markdown of article {}: {}", id, e)))?;
+    let warnings = lint_article(&article.title, &md_contents);
+    if warnings.iter().any(|warning| warning.blocking) {
+        return Err(ArticleWriteError::Lint(warnings));
+    }
+
+    record_initial_revision(db, id, &md_contents).await?;
+
+    // Articles from before authors were recorded are attributed to their first editor.
+    // Publishing keeps the date of the first publication, for articles that were archived and come back.
+    let stored = sqlx::query_as::<_, ArticleEntity>(
+        r#"
+        UPDATE articles
+        SET title = $1, description = $2, md_filename = $3, photo_filename = $4, search_body = $5,
+            updated_at = now(), author_id = COALESCE(author_id, $6), status = $8, publish_at = $9,
+            published_at = CASE WHEN $8 = $10 THEN COALESCE(published_at, now()) ELSE published_at END,
+            version = version + 1
+        WHERE id = $7 AND ($11::INTEGER IS NULL OR version = $11)
+        RETURNING *
+        "#
+    )
+        .bind(&article.title)
+        .bind(&article.description)
+        .bind(&article.md_filename)
+        .bind(&article.photo_filename)
+        .bind(markdown_to_text(&md_contents))
+        .bind(author_id)
+        .bind(id)  // Bind the path parameter to the query
+        .bind::<i32>(status.into())
+        .bind(publish_at)
+        .bind::<i32>(ArticleStatus::Published.into())
+        .bind(expected_version)
+        .fetch_optional(db)
+        .await?;
+    let Some(mut stored) = stored else {
+        return Err(version_mismatch(db, id).await);
+    };
+
+    // A new slug leaves the old one behind as a redirect
+    if let Some(slug) = requested_slug.filter(|slug| *slug != stored.slug) {
+        slugs::change_slug(db, id, &slug).await?;
+        stored.slug = slug;
+    }
+
+    // Tags are only replaced when the request lists them
+    let tags = match &tag_names {
+        Some(tag_names) => set_article_tags(db, id, tag_names).await?,
+        None => tags_of(id, &load_article_tags(db, &[id]).await?),
+    };
+    record_revision(db, id, &md_contents, author_id).await?;
+    refresh_related_or_log(db, id).await;
+
+    Ok(ArticleWriteResponse { article: stored, tags, warnings })
+}
+
 //#[post("/articles")]
 pub async fn create_article(
     state: Data<AppState>,
+    claims: ReqData<Claims>,
     mut payload: Multipart,
 ) -> Result<HttpResponse, Error> {
     let mut new_article: Option<ArticleCreateRequest> = None;
@@ -138,162 +814,230 @@ pub async fn create_article(
         actix_web::error::ErrorBadRequest("Missing article data")
     })?;
 
-    // Insert the article into the database
-    let id = match sqlx::query(
-        r#"
-        INSERT INTO articles (title, description, article_type)
-        VALUES ($1, $2, $3)
-        RETURNING id
-        "#
-    )
-        .bind(&new_article.title)
-        .bind(&new_article.description)
-        .bind(new_article.article_type)
-        .fetch_one(&state.db)
+    match save_new_article(&state.db, &new_article, markdown_content.as_deref(), photo_data.as_deref(), &claims.sub).await {
+        Ok(response) => {
+            log_with_colors("INFO", "POST 200 /articles");
+            Ok(HttpResponse::Created().insert_header((ETAG, article_etag(response.article.version))).json(response))
+        }
+        Err(e) => Ok(e.response("POST /articles")),
+    }
+}
+
+
+//TODO NEEDS TESTING
+//#[put("/articles/{id}")]
+pub async fn update_article(
+    req: HttpRequest,
+    state: Data<AppState>,
+    claims: ReqData<Claims>,
+    id: Path<i32>,
+    updated_article: Json<ArticleUpdateRequest>,
+) -> impl Responder {
+    let expected_version = match required_version(&req) {
+        Ok(version) => version,
+        Err(e) => return e.response("PUT /article"),
+    };
+
+    match save_article_update(&state.db, id.into_inner(), updated_article.into_inner(), expected_version, &claims.sub).await {
+        Ok(response) => {
+            log_with_colors("INFO", "PUT 200 /article");
+            HttpResponse::Ok().insert_header((ETAG, article_etag(response.article.version))).json(response)
+        }
+        Err(e) => e.response("PUT /article"),
+    }
+}
+
+
+// Moves the article to the trash, it is purged with its files after the retention
+//#[delete("/articles/{id}")]
+pub async fn delete_article(
+    req: HttpRequest,
+    state: Data<AppState>,
+    claims: ReqData<Claims>,
+    id: Path<i32>,
+) -> impl Responder {
+    if let Err(response) = require_editor(&state.db, &claims.sub, "DELETE /article").await {
+        return response;
+    }
+    let expected_version = match required_version(&req) {
+        Ok(version) => version,
+        Err(e) => return e.response("DELETE /article"),
+    };
+
+    match trash_article(&state.db, id.into_inner(), &claims.sub, expected_version).await {
+        Ok(()) => {
+            log_with_colors("INFO", "DELETE 200 /article");
+            HttpResponse::Ok().body("Article moved to the trash")
+        }
+        Err(e) => e.response("DELETE /article"),
+    }
+}
+
+
+// TRANSLATION SERVICES
+
+//#[put("/articles/{id}/translations/{lang}")]
+pub async fn upsert_translation(
+    state: Data<AppState>,
+    claims: ReqData<Claims>,
+    path: Path<(i32, String)>,
+    mut payload: Multipart,
+) -> Result<HttpResponse, Error> {
+    if let Err(response) = require_editor(&state.db, &claims.sub, "PUT /articles/{id}/translations/{lang}").await {
+        return Ok(response);
+    }
+    let (id, language) = path.into_inner();
+    let language = normalize_language(&language).ok_or_else(|| {
+        log_with_colors("WARN", "PUT 400 /articles/{id}/translations/{lang} - Invalid language");
+        actix_web::error::ErrorBadRequest("Invalid language")
+    })?;
+
+    let mut translation: Option<TranslationRequest> = None;
+    let mut markdown_content = None;
+
+    while let Some(field) = payload.next().await {
+        let mut field = field?;
+        let field_name = field.content_disposition().and_then(|cd| cd.get_name()).unwrap_or_default().to_string();
+
+        let mut content = String::new();
+        while let Some(chunk) = field.next().await {
+            content.push_str(&String::from_utf8_lossy(&chunk?));
+        }
+
+        match field_name.as_str() {
+            "translation" => translation = Some(serde_json::from_str(&content)?),
+            "markdown" => markdown_content = Some(content),
+            _ => {}
+        }
+    }
+
+    let translation = translation.ok_or_else(|| {
+        log_with_colors("WARN", "PUT 400 /articles/{id}/translations/{lang} - Missing translation data");
+        actix_web::error::ErrorBadRequest("Missing translation data")
+    })?;
+
+    let original_language = match sqlx::query("SELECT language FROM articles WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
         .await {
-        Ok(record) => record.try_get::<i32, _>("id").unwrap(),
+        Ok(Some(row)) => row.try_get::<String, _>("language").unwrap(),
+        Ok(None) => {
+            log_with_colors("WARN", "PUT 404 /articles/{id}/translations/{lang}");
+            return Ok(HttpResponse::NotFound().body("Article not found"));
+        }
         Err(e) => {
-            log_with_colors("ERROR", &format!("Failed to create article: {}", e));
-            return Ok(HttpResponse::InternalServerError().body("Failed to create article"));
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            return Ok(HttpResponse::InternalServerError().body("Failed to save translation"));
         }
     };
 
-    // Create the upload directory
-    let home_dir = home::home_dir().expect("Failed to get home directory");
-    let upload_dir_buf = home_dir.join("upload");
-    let upload_dir = upload_dir_buf.to_str().expect("Failed to convert upload dir to string");
-    create_dir_all(&upload_dir).map_err(|e| {
-        log_with_colors("ERROR", &format!("Failed to create upload directory: {}", e));
-        actix_web::error::ErrorInternalServerError("Failed to create upload directory")
-    })?;
+    if original_language == language {
+        log_with_colors("WARN", "PUT 400 /articles/{id}/translations/{lang} - Original language");
+        return Ok(HttpResponse::BadRequest().body("This is the original language of the article, update the article instead"));
+    }
+
+    let warnings = lint_article(&translation.title, markdown_content.as_deref().unwrap_or(""));
+    if warnings.iter().any(|warning| warning.blocking) {
+        log_with_colors("WARN", "PUT 422 /articles/{id}/translations/{lang} - Markdown lint failed");
+        return Ok(HttpResponse::UnprocessableEntity().json(serde_json::json!({ "error": "Markdown lint failed", "warnings": warnings })));
+    }
 
-    // Handle markdown file creation
+    let md_filename = format!("{}.{}.md", id, language);
     if let Some(content) = markdown_content {
-        let md_file_path = format!("{}/{}.md", upload_dir, id);
-        let mut file = File::create(&md_file_path).map_err(|e| {
-            log_with_colors("ERROR", &format!("Failed to create markdown file: {}", e));
-            actix_web::error::ErrorInternalServerError("Failed to create markdown file")
-        })?;
-        file.write_all(content.as_bytes()).map_err(|e| {
+        let md_file_path = article_file_path(id, &md_filename);
+        if let Some(parent) = md_file_path.parent() {
+            create_dir_all(parent).map_err(|e| {
+                log_with_colors("ERROR", &format!("Failed to create article directory: {}", e));
+                actix_web::error::ErrorInternalServerError("Failed to create article directory")
+            })?;
+        }
+        std::fs::write(&md_file_path, content).map_err(|e| {
             log_with_colors("ERROR", &format!("Failed to write markdown file: {}", e));
             actix_web::error::ErrorInternalServerError("Failed to write to markdown file")
         })?;
     }
 
-    // Handle photo file creation
-    if let Some(photo_bytes) = photo_data {
-        let photo_file_path = format!("{}/{}.jpg", upload_dir, id);
-        let mut file = File::create(&photo_file_path).map_err(|e| {
-            log_with_colors("ERROR", &format!("Failed to create photo file: {}", e));
-            actix_web::error::ErrorInternalServerError("Failed to create photo file")
-        })?;
-        file.write_all(&photo_bytes).map_err(|e| {
-            log_with_colors("ERROR", &format!("Failed to write photo file: {}", e));
-            actix_web::error::ErrorInternalServerError("Failed to write to photo file")
-        })?;
-    }
-
-    // Create the ArticleEntity instance with the generated ID
-    let article = ArticleEntity {
-        id,
-        title: new_article.title.clone(),
-        description: new_article.description.clone(),
-        md_filename: format!("{}.md", id), // Use the ID for filename
-        photo_filename: format!("{}.jpg", id), // Use the ID for filename
-        article_type: new_article.article_type,
-    };
-
-    // Update the article with the markdown and photo filenames
-    match sqlx::query(
+    match sqlx::query_as::<_, ArticleTranslationEntity>(
         r#"
-        UPDATE articles
-        SET md_filename = $1, photo_filename = $2
-        WHERE id = $3
+        WITH translation AS (
+            INSERT INTO article_translations (article_id, language, title, description, md_filename)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (article_id, language)
+            DO UPDATE SET title = EXCLUDED.title, description = EXCLUDED.description, md_filename = EXCLUDED.md_filename
+            RETURNING *
+        ), bumped AS (
+            -- GET /articles/{slug} answers with the translation, its ETag changes with it
+            UPDATE articles SET version = version + 1 WHERE id = $1
+        )
+        SELECT * FROM translation
         "#
     )
-        .bind(&article.md_filename)
-        .bind(&article.photo_filename)
         .bind(id)
-        .execute(&state.db)
+        .bind(&language)
+        .bind(&translation.title)
+        .bind(&translation.description)
+        .bind(&md_filename)
+        .fetch_one(&state.db)
         .await {
-        Ok(_) => {
-            log_with_colors("INFO", "POST 200 /articles");
-            Ok(HttpResponse::Created().json(article))
+        Ok(translation) => {
+            log_with_colors("INFO", "PUT 200 /articles/{id}/translations/{lang}");
+            Ok(HttpResponse::Ok().json(serde_json::json!({ "translation": translation, "warnings": warnings })))
         }
         Err(e) => {
-            log_with_colors("WARN", "POST 404 /articles - Article title and description added, failed to add md and photo filename");
-            Ok(HttpResponse::InternalServerError().body("Failed to update article filenames"))
+            log_with_colors("ERROR", &format!("Failed to save translation: {}", e));
+            Ok(HttpResponse::InternalServerError().body("Failed to save translation"))
         }
     }
 }
 
-
-//TODO NEEDS TESTING
-//#[put("/articles/{id}")]
-pub async fn update_article(
+//#[delete("/articles/{id}/translations/{lang}")]
+pub async fn delete_translation(
     state: Data<AppState>,
-    id: Path<i32>,
-    updated_article: Json<ArticleEntity>,
+    claims: ReqData<Claims>,
+    path: Path<(i32, String)>,
 ) -> impl Responder {
-    let article = updated_article.into_inner();
-
-    match sqlx::query(
-        "UPDATE articles SET title = $1, description = $2, md_filename = $3, photo_filename = $4 WHERE id = $5"
-    )
-        .bind(&article.title)
-        .bind(&article.description)
-        .bind(&article.md_filename)
-        .bind(&article.photo_filename)
-        .bind(id.into_inner())  // Bind the path parameter to the query
-        .execute(&state.db)
-        .await
-    {
-        Ok(result) if result.rows_affected() > 0 => {
-            log_with_colors("INFO", "PUT 200 /article");
-            HttpResponse::Ok().body("Article updated successfully")
-        }
-        Ok(_) => {
-            log_with_colors("WARN", "PUT 404 /article");
-            HttpResponse::NotFound().body("Article not found")
-        }
-        Err(_) => {
-            log_with_colors("ERROR", "PUT 500 /article");
-            HttpResponse::InternalServerError().body("Failed to update article")
-        }
+    if let Err(response) = require_editor(&state.db, &claims.sub, "DELETE /articles/{id}/translations/{lang}").await {
+        return response;
     }
-}
-
+    let (id, language) = path.into_inner();
+    let language = normalize_language(&language).unwrap_or(language);
 
-//TODO NEED TO DELETE THE FILES AS WELL AND NEEDS TESTING
-//#[delete("/articles/{id}")]
-pub async fn delete_article(
-    state: Data<AppState>,
-    id: Path<i32>,
-) -> impl Responder {
-    match sqlx::query(
-        "DELETE FROM articles WHERE id = $1"
+    match sqlx::query_as::<_, ArticleTranslationEntity>(
+        r#"
+        WITH translation AS (
+            DELETE FROM article_translations WHERE article_id = $1 AND language = $2 RETURNING *
+        ), bumped AS (
+            UPDATE articles SET version = version + 1 WHERE id IN (SELECT article_id FROM translation)
+        )
+        SELECT * FROM translation
+        "#
     )
-        .bind(id.into_inner())  // Bind the id parameter to the query
-        .execute(&state.db)
+        .bind(id)
+        .bind(&language)
+        .fetch_optional(&state.db)
         .await
     {
-        Ok(result) if result.rows_affected() > 0 => {
-            log_with_colors("INFO", "DELETE 200 /article");
-            HttpResponse::Ok().body("Article deleted successfully")
+        Ok(Some(translation)) => {
+            if let Err(e) = std::fs::remove_file(article_file_path(id, &translation.md_filename)) {
+                log_with_colors("WARN", &format!("Failed to remove translation markdown: {}", e));
+            }
+            log_with_colors("INFO", "DELETE 200 /articles/{id}/translations/{lang}");
+            HttpResponse::Ok().body("Translation deleted successfully")
         }
-        Ok(_) => {
-            log_with_colors("WARN", "DELETE 404 /article");
-            HttpResponse::NotFound().body("Article not found")
+        Ok(None) => {
+            log_with_colors("WARN", "DELETE 404 /articles/{id}/translations/{lang}");
+            HttpResponse::NotFound().body("Translation not found")
         }
         Err(_) => {
-            log_with_colors("ERROR", "DELETE 500 /article");
-            HttpResponse::InternalServerError().body("Failed to delete article")
+            log_with_colors("ERROR", "DELETE 500 /articles/{id}/translations/{lang}");
+            HttpResponse::InternalServerError().body("Failed to delete translation")
         }
     }
 }
 
 
 // LOGIN SERVICES
+
 pub async fn login(db_pool: Data<AppState>, data: Json<LoginRequest>) -> impl Responder {
     let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = $1")
         .bind(&data.email)
@@ -314,7 +1058,17 @@ pub async fn login(db_pool: Data<AppState>, data: Json<LoginRequest>) -> impl Re
     HttpResponse::Unauthorized().finish()
 }
 
-pub async fn signup(db_pool: Data<AppState>, data: Json<SignupRequest>) -> impl Responder {
+// Only editors add users, as contributors unless they ask for another role
+pub async fn signup(db_pool: Data<AppState>, claims: ReqData<Claims>, data: Json<SignupRequest>) -> impl Responder {
+    if let Err(response) = require_editor(&db_pool.db, &claims.sub, "POST /sign-up").await {
+        return response;
+    }
+    let role = data.role.unwrap_or(UserRole::Contributor.into());
+    if !(0..=1).contains(&role) {
+        log_with_colors("WARN", "POST 400 /sign-up");
+        return HttpResponse::BadRequest().body(format!("Unknown role {}", role));
+    }
+
     // Check if the user already exists
     let user_exists = sqlx::query("SELECT 1 FROM users WHERE email = $1")
         .bind(&data.email)
@@ -331,9 +1085,10 @@ pub async fn signup(db_pool: Data<AppState>, data: Json<SignupRequest>) -> impl
     let hashed_password = hash(&data.password, DEFAULT_COST).unwrap();
 
     // Insert the new user into the database
-    sqlx::query("INSERT INTO users (email, password_hash) VALUES ($1, $2)")
+    sqlx::query("INSERT INTO users (email, password_hash, role) VALUES ($1, $2, $3)")
         .bind(&data.email)
         .bind(&hashed_password)
+        .bind(role)
         .execute(&db_pool.db)
         .await
         .unwrap();
@@ -0,0 +1,338 @@
+use std::env;
+use std::path::{Path as FsPath, PathBuf};
+use actix_files::Files;
+use actix_web::http::StatusCode;
+use actix_web::web::{get, to, Data, Path, Query, ServiceConfig};
+use actix_web::{HttpRequest, HttpResponse};
+use serde::Serialize;
+use tera::{Context, Tera};
+use crate::entities::{ArticleEntity, ArticleResponse, RelatedArticle, SeriesInfo};
+use crate::enums::ArticleType;
+use crate::services::{load_article, load_articles, load_articles_by_type};
+use crate::i18n::{requested_languages, LanguageQuery};
+use crate::metadata::ArticleMeta;
+use crate::slugs::{self, SlugLookup};
+use crate::utils::{article_photo_path, article_url, log_with_colors, markdown_to_html, site_title, site_url};
+use crate::AppState;
+
+pub const ARTICLE_TYPES: [ArticleType; 3] = [ArticleType::Important, ArticleType::Favourite, ArticleType::Common];
+
+// Server-rendered HTML pages, enabled by pointing SITE_THEME_DIR at a theme.
+// A theme holds its tera templates under templates/ and its assets under static/.
+// The same templates are used by the build-static command.
+pub struct Site {
+    tera: Tera,
+    pub(crate) theme_dir: PathBuf,
+    title: String,
+}
+
+// How pages link to each other: absolute routes when served by actix,
+// relative file paths when written out by build-static
+pub enum Links {
+    Server,
+    Static { depth: usize },
+}
+
+impl Links {
+    pub fn root(&self) -> String {
+        match self {
+            Links::Server => "/".to_string(),
+            Links::Static { depth: 0 } => "./".to_string(),
+            Links::Static { depth } => "../".repeat(*depth),
+        }
+    }
+
+    pub fn home(&self) -> String {
+        match self {
+            Links::Server => "/".to_string(),
+            Links::Static { .. } => format!("{}index.html", self.root()),
+        }
+    }
+
+    pub fn article(&self, slug: &str) -> String {
+        match self {
+            Links::Server => format!("/posts/{}", slug),
+            Links::Static { .. } => format!("{}posts/{}/index.html", self.root(), slug),
+        }
+    }
+
+    pub fn article_type(&self, article_type: ArticleType) -> String {
+        match self {
+            Links::Server => format!("/types/{}", article_type.slug()),
+            Links::Static { .. } => format!("{}types/{}/index.html", self.root(), article_type.slug()),
+        }
+    }
+
+    // Listing pages only paginate in the static build, `base` is "" for the home listing
+    // or e.g. "types/important/" for a type listing
+    pub fn listing_page(&self, base: &str, page: usize) -> String {
+        if page <= 1 {
+            format!("{}{}index.html", self.root(), base)
+        } else {
+            format!("{}{}page/{}/index.html", self.root(), base, page)
+        }
+    }
+
+    // Absolute link for feeds, sitemaps and canonical tags, a static build points at its own pages
+    pub fn absolute_article(&self, slug: &str) -> String {
+        match self {
+            Links::Server => article_url(slug),
+            Links::Static { .. } => format!("{}/posts/{}/", site_url(), slug),
+        }
+    }
+
+    // Absolute photo URL, the API route when served and the copied media file in a static build
+    pub fn absolute_photo(&self, id: i32) -> Option<String> {
+        match self {
+            Links::Server => ArticleMeta::served_image(id),
+            Links::Static { .. } => article_photo_path(id).exists().then(|| format!("{}/media/{}.jpg", site_url(), id)),
+        }
+    }
+}
+
+// An article as shown in the listing pages
+#[derive(Serialize)]
+struct ArticleCard<'a> {
+    #[serde(flatten)]
+    article: &'a ArticleEntity,
+    url: String,
+    type_slug: &'static str,
+    type_url: String,
+}
+
+// A related article under the article page
+#[derive(Serialize)]
+struct RelatedLink<'a> {
+    #[serde(flatten)]
+    article: &'a RelatedArticle,
+    url: String,
+}
+
+// Position in the series and links to the neighbouring parts
+#[derive(Serialize)]
+struct SeriesNav<'a> {
+    #[serde(flatten)]
+    series: &'a SeriesInfo,
+    previous_url: Option<String>,
+    next_url: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TypeLink {
+    slug: &'static str,
+    url: String,
+}
+
+#[derive(Serialize)]
+pub struct Pagination {
+    pub(crate) page: usize,
+    pub(crate) pages: usize,
+    pub(crate) prev_url: Option<String>,
+    pub(crate) next_url: Option<String>,
+}
+
+impl Site {
+    pub fn from_env() -> Option<Site> {
+        let theme_dir = env::var("SITE_THEME_DIR").ok()?;
+        Some(Site::load(FsPath::new(&theme_dir)))
+    }
+
+    pub fn load(theme_dir: &FsPath) -> Site {
+        let templates = theme_dir.join("templates/**/*.html");
+        let tera = Tera::new(templates.to_str().expect("Failed to convert template path to string"))
+            .unwrap_or_else(|e| panic!("Failed to load templates from {}: {}", theme_dir.display(), e));
+
+        log_with_colors("INFO", &format!("Loaded theme {}", theme_dir.display()));
+
+        Site {
+            tera,
+            theme_dir: theme_dir.to_path_buf(),
+            title: site_title(),
+        }
+    }
+
+    fn context(&self, links: &Links) -> Context {
+        let mut context = Context::new();
+        context.insert("site_title", &self.title);
+        context.insert("root", &links.root());
+        context.insert("home_url", &links.home());
+        context.insert("article_types", &ARTICLE_TYPES
+            .iter()
+            .map(|article_type| TypeLink {
+                slug: article_type.slug(),
+                url: links.article_type(*article_type),
+            })
+            .collect::<Vec<_>>());
+        context
+    }
+
+    pub fn render_listing(
+        &self,
+        links: &Links,
+        article_type: Option<ArticleType>,
+        articles: &[ArticleEntity],
+        pagination: Option<Pagination>,
+    ) -> tera::Result<String> {
+        let mut context = self.context(links);
+        // An article with a type that does not exist is left out instead of failing the whole page
+        let cards: Vec<ArticleCard> = articles
+            .iter()
+            .filter_map(|article| match ArticleType::try_from(article.article_type) {
+                Ok(card_type) => Some(ArticleCard {
+                    article,
+                    url: links.article(&article.slug),
+                    type_slug: card_type.slug(),
+                    type_url: links.article_type(card_type),
+                }),
+                Err(e) => {
+                    log_with_colors("WARN", &format!("Skipping article {} in the listing: {}", article.id, e));
+                    None
+                }
+            })
+            .collect();
+        context.insert("articles", &cards);
+        context.insert("pagination", &pagination);
+
+        match article_type {
+            Some(article_type) => {
+                context.insert("type_slug", article_type.slug());
+                self.tera.render("type.html", &context)
+            }
+            None => self.tera.render("home.html", &context),
+        }
+    }
+
+    // `photo_url` is a data URI when served and a copied media file in the static build
+    pub fn render_article(&self, links: &Links, response: &ArticleResponse, photo_url: Option<String>) -> tera::Result<String> {
+        let article_type = ArticleType::try_from(response.article.article_type).map_err(tera::Error::msg)?;
+
+        let mut context = self.context(links);
+        context.insert("article", &response.article);
+        context.insert("type_slug", article_type.slug());
+        context.insert("type_url", &links.article_type(article_type));
+        context.insert("content", &markdown_to_html(&response.md_contents));
+        context.insert("photo_url", &photo_url);
+        context.insert("related", &response.related
+            .iter()
+            .map(|article| RelatedLink { article, url: links.article(&article.slug) })
+            .collect::<Vec<_>>());
+        context.insert("series", &response.series.as_ref().map(|series| SeriesNav {
+            series,
+            previous_url: series.previous.as_ref().map(|part| links.article(&part.slug)),
+            next_url: series.next.as_ref().map(|part| links.article(&part.slug)),
+        }));
+
+        // Link previews need absolute URLs, which depend on where the page is hosted
+        let meta = ArticleMeta::new(
+            &response.article,
+            links.absolute_article(&response.article.slug),
+            photo_url.as_ref().and_then(|_| links.absolute_photo(response.article.id)),
+        );
+        context.insert("meta", &meta.head_html());
+        self.tera.render("article.html", &context)
+    }
+
+    pub fn render_not_found(&self, links: &Links) -> tera::Result<String> {
+        self.tera.render("404.html", &self.context(links))
+    }
+
+    fn respond(&self, status: StatusCode, rendered: tera::Result<String>) -> HttpResponse {
+        match rendered {
+            Ok(body) => HttpResponse::build(status).content_type("text/html; charset=utf-8").body(body),
+            Err(e) => {
+                log_with_colors("ERROR", &format!("Failed to render page: {}", e));
+                self.error_page()
+            }
+        }
+    }
+
+    fn not_found_page(&self) -> HttpResponse {
+        self.respond(StatusCode::NOT_FOUND, self.render_not_found(&Links::Server))
+    }
+
+    fn error_page(&self) -> HttpResponse {
+        match self.tera.render("500.html", &self.context(&Links::Server)) {
+            Ok(body) => HttpResponse::InternalServerError().content_type("text/html; charset=utf-8").body(body),
+            Err(_) => HttpResponse::InternalServerError().body("Internal server error"),
+        }
+    }
+}
+
+pub fn configure(cfg: &mut ServiceConfig, site: Data<Site>) {
+    let static_dir = site.theme_dir.join("static");
+
+    cfg.app_data(site)
+        .route("/", get().to(home))
+        .route("/posts/{slug}", get().to(article_page))
+        .route("/types/{article_type}", get().to(type_page))
+        .service(Files::new("/static", static_dir))
+        .default_service(to(not_found));
+}
+
+pub async fn home(state: Data<AppState>, site: Data<Site>) -> HttpResponse {
+    match load_articles(&state.db).await {
+        Ok(articles) => {
+            log_with_colors("INFO", "GET 200 /");
+            site.respond(StatusCode::OK, site.render_listing(&Links::Server, None, &articles, None))
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            site.error_page()
+        }
+    }
+}
+
+pub async fn article_page(req: HttpRequest, state: Data<AppState>, site: Data<Site>, key: Path<String>, query: Query<LanguageQuery>) -> HttpResponse {
+    let id = match slugs::lookup(&state.db, &key).await {
+        Ok(SlugLookup::Current(id)) => id,
+        Ok(SlugLookup::Moved { slug, public: true, .. }) => return slugs::redirect(&req, &format!("/posts/{}", slug)),
+        Ok(SlugLookup::Moved { public: false, .. }) | Ok(SlugLookup::NotFound) => {
+            log_with_colors("WARN", "GET 404 /posts/{slug}");
+            return site.not_found_page();
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            return site.error_page();
+        }
+    };
+
+    match load_article(&state.db, id, &requested_languages(&req, &query)).await {
+        Ok(response) if response.article.is_public() => {
+            let photo_url = Some(format!("data:image/jpeg;base64,{}", response.photo_contents))
+                .filter(|_| !response.photo_contents.is_empty());
+
+            log_with_colors("INFO", "GET 200 /posts/{slug}");
+            site.respond(StatusCode::OK, site.render_article(&Links::Server, &response, photo_url))
+        }
+        Ok(_) | Err(sqlx::Error::RowNotFound) => {
+            log_with_colors("WARN", "GET 404 /posts/{slug}");
+            site.not_found_page()
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            site.error_page()
+        }
+    }
+}
+
+pub async fn type_page(state: Data<AppState>, site: Data<Site>, slug: Path<String>) -> HttpResponse {
+    let Some(article_type) = ArticleType::from_slug(&slug) else {
+        log_with_colors("WARN", "GET 404 /types/{type}");
+        return site.not_found_page();
+    };
+
+    match load_articles_by_type(&state.db, article_type.into()).await {
+        Ok(articles) => {
+            log_with_colors("INFO", "GET 200 /types/{type}");
+            site.respond(StatusCode::OK, site.render_listing(&Links::Server, Some(article_type), &articles, None))
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            site.error_page()
+        }
+    }
+}
+
+pub async fn not_found(site: Data<Site>) -> HttpResponse {
+    site.not_found_page()
+}
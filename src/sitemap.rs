@@ -0,0 +1,153 @@
+use std::env;
+use actix_web::web::{Data, Path};
+use actix_web::{HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use crate::services::load_articles;
+use crate::site::Links;
+use crate::utils::{cached_response, log_with_colors, read_file_contents, site_url};
+use crate::AppState;
+
+// The sitemap protocol allows at most 50,000 URLs per file, above that /sitemap.xml
+// becomes a sitemap index pointing at /sitemap-1.xml, /sitemap-2.xml, ...
+pub const SITEMAP_LIMIT: usize = 50_000;
+
+pub struct SitemapEntry {
+    pub(crate) url: String,
+    pub(crate) lastmod: Option<DateTime<Utc>>,
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+pub async fn load_sitemap_entries(db: &PgPool, links: &Links) -> Result<Vec<SitemapEntry>, sqlx::Error> {
+    let mut articles = load_articles(db).await?;
+    articles.sort_by_key(|article| article.id);
+
+    Ok(articles
+        .iter()
+        .map(|article| SitemapEntry {
+            url: links.absolute_article(&article.slug),
+            lastmod: Some(article.updated_at),
+        })
+        .collect())
+}
+
+pub fn page_count(entries: &[SitemapEntry]) -> usize {
+    entries.len().div_ceil(SITEMAP_LIMIT).max(1)
+}
+
+pub fn last_modified(entries: &[SitemapEntry]) -> Option<DateTime<Utc>> {
+    entries.iter().filter_map(|entry| entry.lastmod).max()
+}
+
+// A single <urlset> with the entries of `page` (1-based)
+pub fn build_urlset(entries: &[SitemapEntry], page: usize) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+
+    for entry in entries.iter().skip((page - 1) * SITEMAP_LIMIT).take(SITEMAP_LIMIT) {
+        xml.push_str("  <url>\n");
+        xml.push_str(&format!("    <loc>{}</loc>\n", xml_escape(&entry.url)));
+        if let Some(lastmod) = entry.lastmod {
+            xml.push_str(&format!("    <lastmod>{}</lastmod>\n", lastmod.format("%Y-%m-%dT%H:%M:%S+00:00")));
+        }
+        xml.push_str("  </url>\n");
+    }
+
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+pub fn build_index(entries: &[SitemapEntry]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+
+    for (index, chunk) in entries.chunks(SITEMAP_LIMIT).enumerate() {
+        xml.push_str("  <sitemap>\n");
+        xml.push_str(&format!("    <loc>{}</loc>\n", xml_escape(&format!("{}/sitemap-{}.xml", site_url(), index + 1))));
+        if let Some(lastmod) = last_modified(chunk) {
+            xml.push_str(&format!("    <lastmod>{}</lastmod>\n", lastmod.format("%Y-%m-%dT%H:%M:%S+00:00")));
+        }
+        xml.push_str("  </sitemap>\n");
+    }
+
+    xml.push_str("</sitemapindex>\n");
+    xml
+}
+
+// /sitemap.xml is the urlset itself while everything fits in one file
+pub fn build_sitemap(entries: &[SitemapEntry]) -> String {
+    if page_count(entries) > 1 {
+        build_index(entries)
+    } else {
+        build_urlset(entries, 1)
+    }
+}
+
+// ROBOTS_TXT_FILE replaces the generated file entirely, otherwise every path is allowed
+// except the ones in ROBOTS_DISALLOW (comma separated, /protected/ by default)
+pub fn build_robots() -> String {
+    if let Ok(file) = env::var("ROBOTS_TXT_FILE") {
+        match read_file_contents(&file) {
+            Ok(contents) => return contents,
+            Err(e) => log_with_colors("ERROR", &format!("Failed to read ROBOTS_TXT_FILE {}: {}", file, e)),
+        }
+    }
+
+    let disallow = env::var("ROBOTS_DISALLOW").unwrap_or_else(|_| "/protected/".to_string());
+
+    let mut robots = String::from("User-agent: *\n");
+    for path in disallow.split(',').map(str::trim).filter(|path| !path.is_empty()) {
+        robots.push_str(&format!("Disallow: {}\n", path));
+    }
+    robots.push_str("Allow: /\n\n");
+    robots.push_str(&format!("Sitemap: {}/sitemap.xml\n", site_url()));
+    robots
+}
+
+//#[get("/sitemap.xml")]
+pub async fn sitemap(req: HttpRequest, state: Data<AppState>) -> HttpResponse {
+    match load_sitemap_entries(&state.db, &Links::Server).await {
+        Ok(entries) => {
+            log_with_colors("INFO", "GET 200 /sitemap.xml");
+            cached_response(&req, "application/xml; charset=utf-8", build_sitemap(&entries), last_modified(&entries))
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to build sitemap")
+        }
+    }
+}
+
+//#[get("/sitemap-{page}.xml")]
+pub async fn sitemap_page(req: HttpRequest, state: Data<AppState>, page: Path<usize>) -> HttpResponse {
+    let page = page.into_inner();
+
+    match load_sitemap_entries(&state.db, &Links::Server).await {
+        Ok(entries) if page >= 1 && page <= page_count(&entries) => {
+            log_with_colors("INFO", "GET 200 /sitemap-{page}.xml");
+            cached_response(&req, "application/xml; charset=utf-8", build_urlset(&entries, page), last_modified(&entries))
+        }
+        Ok(_) => {
+            log_with_colors("WARN", "GET 404 /sitemap-{page}.xml");
+            HttpResponse::NotFound().body("Sitemap page not found")
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to build sitemap")
+        }
+    }
+}
+
+//#[get("/robots.txt")]
+pub async fn robots() -> HttpResponse {
+    log_with_colors("INFO", "GET 200 /robots.txt");
+    HttpResponse::Ok().content_type("text/plain; charset=utf-8").body(build_robots())
+}
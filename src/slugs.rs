@@ -0,0 +1,181 @@
+use actix_web::http::header::LOCATION;
+use actix_web::{HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use crate::enums::ArticleStatus;
+use crate::utils::{log_with_colors, slugify};
+
+const MAX_SLUG_LENGTH: usize = 200;
+const MAX_GENERATED_LENGTH: usize = 80; // generated slugs are cut at a word boundary below this
+
+// Where an article key from a URL leads: the article itself when it is the current
+// slug, or the article and its current slug when it is a numeric id or an old slug.
+// The current slug carries the title, callers only redirect to it when `public` or previewing
+pub enum SlugLookup {
+    Current(i32),
+    Moved { id: i32, slug: String, public: bool },
+    NotFound,
+}
+
+// Slug for a new article, derived from its title
+pub fn slug_from_title(title: &str) -> String {
+    let mut slug = String::new();
+    for word in slugify(title).split('-') {
+        if !slug.is_empty() && slug.len() + 1 + word.len() > MAX_GENERATED_LENGTH {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(word);
+    }
+    slug.truncate(MAX_GENERATED_LENGTH);
+
+    // A purely numeric slug would be taken for an id
+    if slug.is_empty() {
+        "article".to_string()
+    } else if slug.chars().all(|c| c.is_ascii_digit()) {
+        format!("article-{}", slug)
+    } else {
+        slug
+    }
+}
+
+// A slug chosen by an editor has to be in the form slugify would produce
+pub fn validate_slug(slug: &str) -> Result<(), String> {
+    if slug.len() > MAX_SLUG_LENGTH {
+        return Err(format!("Slug is longer than {} characters", MAX_SLUG_LENGTH));
+    }
+    if slugify(slug) != slug {
+        return Err(format!("Invalid slug {}, use lowercase letters, digits and single hyphens", slug));
+    }
+    if slug.chars().all(|c| c.is_ascii_digit()) {
+        return Err("A slug cannot be only digits".to_string());
+    }
+    Ok(())
+}
+
+// Whether another article holds the slug, now or in its history
+pub async fn slug_taken(db: &PgPool, slug: &str, article_id: Option<i32>) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (SELECT 1 FROM articles WHERE slug = $1 AND id IS DISTINCT FROM $2)
+            OR EXISTS (SELECT 1 FROM article_slug_history WHERE slug = $1 AND article_id IS DISTINCT FROM $2)
+        "#
+    )
+        .bind(slug)
+        .bind(article_id)
+        .fetch_one(db)
+        .await
+}
+
+// `base`, or `base-2`, `base-3`, ... when it is taken
+pub async fn unique_slug(db: &PgPool, base: &str, article_id: Option<i32>) -> Result<String, sqlx::Error> {
+    let mut candidate = base.to_string();
+    let mut suffix = 1;
+    while slug_taken(db, &candidate, article_id).await? {
+        suffix += 1;
+        candidate = format!("{}-{}", base, suffix);
+    }
+    Ok(candidate)
+}
+
+// Moves the article to `slug`, its current slug keeps redirecting
+pub async fn change_slug(db: &PgPool, article_id: i32, slug: &str) -> Result<(), sqlx::Error> {
+    let mut transaction = db.begin().await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO article_slug_history (slug, article_id)
+        SELECT slug, id FROM articles WHERE id = $1 AND slug IS NOT NULL AND slug <> $2
+        ON CONFLICT (slug) DO UPDATE SET replaced_at = now()
+        "#
+    )
+        .bind(article_id)
+        .bind(slug)
+        .execute(&mut *transaction)
+        .await?;
+
+    // Going back to an earlier slug takes it out of the history
+    sqlx::query("DELETE FROM article_slug_history WHERE slug = $1 AND article_id = $2")
+        .bind(slug)
+        .bind(article_id)
+        .execute(&mut *transaction)
+        .await?;
+
+    sqlx::query("UPDATE articles SET slug = $1 WHERE id = $2")
+        .bind(slug)
+        .bind(article_id)
+        .execute(&mut *transaction)
+        .await?;
+
+    transaction.commit().await
+}
+
+// Articles stored before slugs existed get one from their title, runs on startup
+pub async fn assign_missing_slugs(db: &PgPool) -> Result<usize, sqlx::Error> {
+    let articles = sqlx::query_as::<_, (i32, String)>(
+        "SELECT id, title FROM articles WHERE slug IS NULL ORDER BY id"
+    )
+        .fetch_all(db)
+        .await?;
+
+    for (id, title) in &articles {
+        let slug = unique_slug(db, &slug_from_title(title), Some(*id)).await?;
+        sqlx::query("UPDATE articles SET slug = $1 WHERE id = $2")
+            .bind(&slug)
+            .bind(id)
+            .execute(db)
+            .await?;
+    }
+
+    Ok(articles.len())
+}
+
+pub async fn load_old_slugs(db: &PgPool, article_id: i32) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>("SELECT slug FROM article_slug_history WHERE article_id = $1 ORDER BY replaced_at")
+        .bind(article_id)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn lookup(db: &PgPool, key: &str) -> Result<SlugLookup, sqlx::Error> {
+    let current = sqlx::query_scalar::<_, i32>("SELECT id FROM articles WHERE slug = $1")
+        .bind(key)
+        .fetch_optional(db)
+        .await?;
+    if let Some(id) = current {
+        return Ok(SlugLookup::Current(id));
+    }
+
+    let moved = match key.parse::<i32>() {
+        Ok(id) => {
+            sqlx::query_as::<_, (i32, String, bool)>("SELECT id, slug, status = $2 FROM articles WHERE id = $1")
+                .bind(id)
+                .bind::<i32>(ArticleStatus::Published.into())
+                .fetch_optional(db)
+                .await?
+        }
+        Err(_) => {
+            sqlx::query_as::<_, (i32, String, bool)>(
+                "SELECT articles.id, articles.slug, articles.status = $2 FROM article_slug_history JOIN articles ON articles.id = article_slug_history.article_id WHERE article_slug_history.slug = $1"
+            )
+                .bind(key)
+                .bind::<i32>(ArticleStatus::Published.into())
+                .fetch_optional(db)
+                .await?
+        }
+    };
+
+    Ok(moved.map_or(SlugLookup::NotFound, |(id, slug, public)| SlugLookup::Moved { id, slug, public }))
+}
+
+// 301 to `path`, keeping the query string so ?lang= and friends survive
+pub fn redirect(req: &HttpRequest, path: &str) -> HttpResponse {
+    let location = match req.query_string() {
+        "" => path.to_string(),
+        query => format!("{}?{}", path, query),
+    };
+
+    log_with_colors("INFO", &format!("GET 301 {}", req.path()));
+    HttpResponse::MovedPermanently().insert_header((LOCATION, location)).finish()
+}
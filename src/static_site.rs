@@ -0,0 +1,296 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use crate::entities::ArticleEntity;
+use crate::enums::ArticleType;
+use crate::feeds::{build_atom, build_json_feed, build_rss, load_feed_entries, FeedOptions};
+use crate::services::{load_article, load_articles};
+use crate::sitemap::{build_robots, build_sitemap, build_urlset, load_sitemap_entries, page_count};
+use crate::site::{Links, Pagination, Site, ARTICLE_TYPES};
+use crate::slugs::load_old_slugs;
+use crate::utils::{article_photo_path, log_with_colors};
+
+const MANIFEST_FILE: &str = "manifest.json";
+const DEFAULT_PAGE_SIZE: usize = 10;
+
+// Written next to the generated files so the next build only re-renders what changed
+#[derive(Serialize, Deserialize, Default)]
+struct Manifest {
+    theme: String,
+    articles: BTreeMap<i32, String>, // article id -> hash of its data, markdown and photo
+    #[serde(default)]
+    pages: BTreeMap<i32, Vec<String>>, // article id -> its directories under posts/, the slug and its redirects
+}
+
+struct BuildOptions {
+    output: PathBuf,
+    theme: PathBuf,
+    full: bool,
+}
+
+impl BuildOptions {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = BuildOptions {
+            output: PathBuf::from("public"),
+            theme: PathBuf::from(env::var("SITE_THEME_DIR").unwrap_or_else(|_| "themes/default".to_string())),
+            full: false,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--output" => options.output = PathBuf::from(args.next().ok_or("--output needs a directory")?),
+                "--theme" => options.theme = PathBuf::from(args.next().ok_or("--theme needs a directory")?),
+                "--full" => options.full = true,
+                other => return Err(format!("Unknown build-static option {}", other)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+fn page_size() -> usize {
+    env::var("SITE_PAGE_SIZE")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|size| *size > 0)
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+}
+
+fn write_file(output: &Path, relative: &str, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let path = output.join(relative);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+// Hash every file of the theme, a changed template or asset means every page is stale
+fn hash_dir(hasher: &mut Sha256, dir: &Path) -> std::io::Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        hasher.update(entry.file_name().to_string_lossy().as_bytes());
+        if entry.file_type()?.is_dir() {
+            hash_dir(hasher, &entry.path())?;
+        } else {
+            hasher.update(fs::read(entry.path())?);
+        }
+    }
+    Ok(())
+}
+
+// Static hosts cannot answer with a 301, a refresh with a canonical link is the closest equivalent
+fn redirect_page(slug: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<link rel=\"canonical\" href=\"{url}\">\n<meta http-equiv=\"refresh\" content=\"0; url=../{slug}/index.html\">\n</head>\n<body><a href=\"../{slug}/index.html\">{url}</a></body>\n</html>\n",
+        url = Links::Static { depth: 2 }.absolute_article(slug),
+        slug = slug,
+    )
+}
+
+fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else if path.exists() {
+        fs::remove_file(path)
+    } else {
+        Ok(())
+    }
+}
+
+// Write `articles` as paginated listing pages under `base` ("" or "types/<slug>/")
+fn build_listing(
+    site: &Site,
+    output: &Path,
+    base: &str,
+    article_type: Option<ArticleType>,
+    articles: &[&ArticleEntity],
+) -> Result<(), Box<dyn Error>> {
+    let size = page_size();
+    let pages = articles.len().div_ceil(size).max(1);
+
+    // Older page counts may have been higher, drop them before writing the new ones
+    remove_if_exists(&output.join(base).join("page"))?;
+
+    for page in 1..=pages {
+        let depth = base.matches('/').count() + if page > 1 { 2 } else { 0 };
+        let links = Links::Static { depth };
+        let pagination = Pagination {
+            page,
+            pages,
+            prev_url: (page > 1).then(|| links.listing_page(base, page - 1)),
+            next_url: (page < pages).then(|| links.listing_page(base, page + 1)),
+        };
+
+        let chunk: Vec<ArticleEntity> = articles
+            .iter()
+            .skip((page - 1) * size)
+            .take(size)
+            .map(|article| (*article).clone())
+            .collect();
+
+        let html = site.render_listing(&links, article_type, &chunk, Some(pagination))?;
+        let relative = if page == 1 {
+            format!("{}index.html", base)
+        } else {
+            format!("{}page/{}/index.html", base, page)
+        };
+        write_file(output, &relative, &html)?;
+    }
+
+    Ok(())
+}
+
+pub async fn build_static(db: &PgPool, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = BuildOptions::parse(args)?;
+    let output = options.output.as_path();
+    let site = Site::load(&options.theme);
+
+    fs::create_dir_all(output)?;
+
+    let manifest_path = output.join(MANIFEST_FILE);
+    let previous: Manifest = fs::read_to_string(&manifest_path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default();
+
+    let mut theme_hasher = Sha256::new();
+    hash_dir(&mut theme_hasher, &options.theme)?;
+    let theme_hash = format!("{:x}", theme_hasher.finalize());
+
+    let full = options.full || previous.theme != theme_hash;
+    if full {
+        log_with_colors("INFO", "Rebuilding every page");
+    }
+
+    let mut manifest = Manifest {
+        theme: theme_hash,
+        articles: BTreeMap::new(),
+        pages: BTreeMap::new(),
+    };
+
+    remove_if_exists(&output.join("static"))?;
+    copy_dir(&options.theme.join("static"), &output.join("static"))?;
+
+    // Newest first, in the order the server's listing pages show them
+    let articles = load_articles(db).await?;
+
+    let mut rebuilt = 0;
+    for article in &articles {
+        let response = load_article(db, article.id, &[]).await?;
+        let photo = fs::read(article_photo_path(article.id)).ok();
+
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_vec(&response.article)?);
+        hasher.update(serde_json::to_vec(&response.related)?);
+        hasher.update(serde_json::to_vec(&response.series)?);
+        hasher.update(response.md_contents.as_bytes());
+        if let Some(photo) = &photo {
+            hasher.update(photo);
+        }
+        let hash = format!("{:x}", hasher.finalize());
+
+        // Numeric ids and old slugs lead to the article like they do on the server
+        let mut pages = vec![article.slug.clone(), article.id.to_string()];
+        pages.extend(load_old_slugs(db, article.id).await?);
+        for redirect in &pages[1..] {
+            write_file(output, &format!("posts/{}/index.html", redirect), redirect_page(&article.slug))?;
+        }
+        for stale in previous.pages.get(&article.id).into_iter().flatten().filter(|page| !pages.contains(page)) {
+            remove_if_exists(&output.join(format!("posts/{}", stale)))?;
+        }
+
+        let page = format!("posts/{}/index.html", article.slug);
+        let unchanged = previous.articles.get(&article.id) == Some(&hash) && output.join(&page).exists();
+        manifest.articles.insert(article.id, hash);
+        manifest.pages.insert(article.id, pages);
+        if unchanged && !full {
+            continue;
+        }
+
+        let links = Links::Static { depth: 2 };
+        let photo_url = match &photo {
+            Some(photo) => {
+                write_file(output, &format!("media/{}.jpg", article.id), photo)?;
+                Some(format!("{}media/{}.jpg", links.root(), article.id))
+            }
+            None => {
+                remove_if_exists(&output.join(format!("media/{}.jpg", article.id)))?;
+                None
+            }
+        };
+
+        write_file(output, &page, &site.render_article(&links, &response, photo_url)?)?;
+        rebuilt += 1;
+    }
+
+    // Articles deleted since the last build
+    for id in previous.articles.keys().filter(|id| !manifest.articles.contains_key(id)) {
+        for page in previous.pages.get(id).into_iter().flatten() {
+            remove_if_exists(&output.join(format!("posts/{}", page)))?;
+        }
+        remove_if_exists(&output.join(format!("posts/{}", id)))?;
+        remove_if_exists(&output.join(format!("media/{}.jpg", id)))?;
+    }
+
+    // Listings depend on every article, so they are always rendered
+    let all: Vec<&ArticleEntity> = articles.iter().collect();
+    build_listing(&site, output, "", None, &all)?;
+
+    for article_type in ARTICLE_TYPES {
+        let type_id: i32 = article_type.into();
+        let of_type: Vec<&ArticleEntity> = articles.iter().filter(|article| article.article_type == type_id).collect();
+        build_listing(&site, output, &format!("types/{}/", article_type.slug()), Some(article_type), &of_type)?;
+    }
+
+    let feed_options = FeedOptions { article_type: None, full_content: true };
+    let feed_entries = load_feed_entries(db, &feed_options).await?;
+    let root_links = Links::Static { depth: 0 };
+    write_file(output, "feed.xml", build_rss(&feed_entries, &feed_options, &root_links))?;
+    write_file(output, "atom.xml", build_atom(&feed_entries, &feed_options, &root_links))?;
+    write_file(output, "feed.json", build_json_feed(&feed_entries, &feed_options, &root_links, 1, false))?;
+
+    let sitemap_entries = load_sitemap_entries(db, &root_links).await?;
+    write_file(output, "sitemap.xml", build_sitemap(&sitemap_entries))?;
+    if page_count(&sitemap_entries) > 1 {
+        for page in 1..=page_count(&sitemap_entries) {
+            write_file(output, &format!("sitemap-{}.xml", page), build_urlset(&sitemap_entries, page))?;
+        }
+    }
+    write_file(output, "robots.txt", build_robots())?;
+
+    write_file(output, "404.html", &site.render_not_found(&root_links)?)?;
+    fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)?;
+
+    log_with_colors("INFO", &format!(
+        "Built {} articles into {}: {} pages rendered, {} unchanged",
+        articles.len(),
+        output.display(),
+        rebuilt,
+        articles.len() - rebuilt
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,321 @@
+use actix_web::web::{Data, Json, Path, Query, ReqData};
+use actix_web::{HttpRequest, HttpResponse};
+use sqlx::PgPool;
+use crate::entities::{ArticleTagEntity, Claims, TagCount, TagEntity, TagMergeRequest, TagRenameRequest};
+use crate::enums::ArticleStatus;
+use crate::i18n::{requested_languages, LanguageQuery};
+use crate::listing::{ArticleListing, ListQuery};
+use crate::pagination::PageQuery;
+use crate::review::require_editor;
+use crate::services::{refresh_related_or_log, respond_with_listing};
+use crate::utils::{log_with_colors, slugify};
+use crate::AppState;
+
+const MAX_TAG_LENGTH: usize = 64;
+
+// A tag name as given by an editor, with the slug that identifies it
+pub struct TagName {
+    pub(crate) slug: String,
+    pub(crate) name: String,
+}
+
+fn parse_tag_name(name: &str) -> Result<TagName, String> {
+    let name = name.trim();
+    let slug = slugify(name);
+    if slug.is_empty() {
+        return Err(format!("Invalid tag {:?}", name));
+    }
+    if name.chars().count() > MAX_TAG_LENGTH || slug.chars().count() > MAX_TAG_LENGTH {
+        return Err(format!("Tag {} is longer than {} characters", name, MAX_TAG_LENGTH));
+    }
+
+    Ok(TagName { slug, name: name.to_string() })
+}
+
+// Validate the tag names of a request, names with the same slug count once
+pub fn parse_tag_names(names: &[String]) -> Result<Vec<TagName>, String> {
+    let mut tags: Vec<TagName> = Vec::new();
+    for name in names {
+        let tag = parse_tag_name(name)?;
+        if !tags.iter().any(|existing| existing.slug == tag.slug) {
+            tags.push(tag);
+        }
+    }
+    Ok(tags)
+}
+
+pub async fn load_article_tags(db: &PgPool, article_ids: &[i32]) -> Result<Vec<ArticleTagEntity>, sqlx::Error> {
+    sqlx::query_as::<_, ArticleTagEntity>(
+        r#"
+        SELECT article_tags.article_id, tags.id, tags.slug, tags.name
+        FROM article_tags
+        JOIN tags ON tags.id = article_tags.tag_id
+        WHERE article_tags.article_id = ANY($1)
+        ORDER BY tags.name
+        "#
+    )
+        .bind(article_ids)
+        .fetch_all(db)
+        .await
+}
+
+pub fn tags_of(article_id: i32, tags: &[ArticleTagEntity]) -> Vec<TagEntity> {
+    tags.iter()
+        .filter(|tag| tag.article_id == article_id)
+        .map(|tag| tag.tag.clone())
+        .collect()
+}
+
+// Replace the tags of an article, creating the ones that do not exist yet
+pub async fn set_article_tags(db: &PgPool, article_id: i32, names: &[TagName]) -> Result<Vec<TagEntity>, sqlx::Error> {
+    let mut transaction = db.begin().await?;
+
+    sqlx::query("DELETE FROM article_tags WHERE article_id = $1")
+        .bind(article_id)
+        .execute(&mut *transaction)
+        .await?;
+
+    let mut tags = Vec::new();
+    for name in names {
+        // An existing tag keeps its name, DO UPDATE only makes RETURNING yield the row
+        let tag = sqlx::query_as::<_, TagEntity>(
+            r#"
+            INSERT INTO tags (slug, name)
+            VALUES ($1, $2)
+            ON CONFLICT (slug) DO UPDATE SET slug = EXCLUDED.slug
+            RETURNING *
+            "#
+        )
+            .bind(&name.slug)
+            .bind(&name.name)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        sqlx::query("INSERT INTO article_tags (article_id, tag_id) VALUES ($1, $2)")
+            .bind(article_id)
+            .bind(tag.id)
+            .execute(&mut *transaction)
+            .await?;
+
+        tags.push(tag);
+    }
+
+    transaction.commit().await?;
+    tags.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(tags)
+}
+
+async fn find_tag(db: &PgPool, slug: &str) -> Result<Option<TagEntity>, sqlx::Error> {
+    sqlx::query_as::<_, TagEntity>("SELECT * FROM tags WHERE slug = $1")
+        .bind(slug)
+        .fetch_optional(db)
+        .await
+}
+
+async fn load_tag_count(db: &PgPool, id: i32) -> Result<TagCount, sqlx::Error> {
+    sqlx::query_as::<_, TagCount>(
+        r#"
+        SELECT tags.*, (
+            SELECT COUNT(*) FROM article_tags JOIN articles ON articles.id = article_tags.article_id
+            WHERE article_tags.tag_id = tags.id AND articles.status = $2
+        ) AS article_count
+        FROM tags
+        WHERE id = $1
+        "#
+    )
+        .bind(id)
+        .bind::<i32>(ArticleStatus::Published.into())
+        .fetch_one(db)
+        .await
+}
+
+//#[get("/tags")]
+pub async fn fetch_tags(state: Data<AppState>) -> HttpResponse {
+    match sqlx::query_as::<_, TagCount>(
+        r#"
+        SELECT tags.*, COUNT(articles.id) AS article_count
+        FROM tags
+        LEFT JOIN article_tags ON article_tags.tag_id = tags.id
+        LEFT JOIN articles ON articles.id = article_tags.article_id AND articles.status = $1
+        GROUP BY tags.id
+        ORDER BY tags.name
+        "#
+    )
+        .bind::<i32>(ArticleStatus::Published.into())
+        .fetch_all(&state.db)
+        .await
+    {
+        Ok(tags) => {
+            log_with_colors("INFO", "GET 200 /tags");
+            HttpResponse::Ok().json(tags)
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to load tags")
+        }
+    }
+}
+
+// Same paging, sorting and filters as GET /articles, restricted to one tag
+//#[get("/tags/{slug}/articles")]
+pub async fn fetch_tag_articles(
+    req: HttpRequest,
+    state: Data<AppState>,
+    slug: Path<String>,
+    query: Query<LanguageQuery>,
+    page_query: Query<PageQuery>,
+    list_query: Query<ListQuery>,
+) -> HttpResponse {
+    let slug = slug.into_inner();
+
+    match find_tag(&state.db, &slug).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            log_with_colors("WARN", "GET 404 /tags/{slug}/articles");
+            return HttpResponse::NotFound().body("Tag not found");
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            return HttpResponse::InternalServerError().body("Failed to load tag");
+        }
+    }
+
+    let listing = ArticleListing::from_query(&list_query).map(|listing| ArticleListing { tag: Some(slug), ..listing });
+    let languages = requested_languages(&req, &query);
+    respond_with_listing(&req, &state.db, &languages, listing, &page_query, "/tags/{slug}/articles").await
+}
+
+// Renaming also changes the slug, a name whose slug is taken by another tag needs a merge instead
+//#[put("/tags/{slug}")]
+pub async fn rename_tag(state: Data<AppState>, claims: ReqData<Claims>, slug: Path<String>, request: Json<TagRenameRequest>) -> HttpResponse {
+    if let Err(response) = require_editor(&state.db, &claims.sub, "PUT /tags/{slug}").await {
+        return response;
+    }
+    let name = match parse_tag_name(&request.name) {
+        Ok(name) => name,
+        Err(message) => {
+            log_with_colors("WARN", "PUT 400 /tags/{slug}");
+            return HttpResponse::BadRequest().body(message);
+        }
+    };
+
+    let renamed = sqlx::query_as::<_, TagEntity>(
+        r#"
+        WITH renamed AS (
+            UPDATE tags SET slug = $1, name = $2 WHERE slug = $3 RETURNING *
+        ), bumped AS (
+            -- The tags are part of every tagged article, their ETags change with them.
+            -- Related articles are scored on tag ids, which a rename keeps.
+            UPDATE articles SET version = version + 1
+            WHERE id IN (SELECT article_id FROM article_tags WHERE tag_id IN (SELECT id FROM renamed))
+        )
+        SELECT * FROM renamed
+        "#
+    )
+        .bind(&name.slug)
+        .bind(&name.name)
+        .bind(slug.as_str())
+        .fetch_optional(&state.db)
+        .await;
+
+    match renamed {
+        Ok(Some(tag)) => match load_tag_count(&state.db, tag.id).await {
+            Ok(tag) => {
+                log_with_colors("INFO", "PUT 200 /tags/{slug}");
+                HttpResponse::Ok().json(tag)
+            }
+            Err(e) => {
+                log_with_colors("ERROR", &format!("Database query failed: {}", e));
+                HttpResponse::InternalServerError().body("Failed to load tag")
+            }
+        },
+        Ok(None) => {
+            log_with_colors("WARN", "PUT 404 /tags/{slug}");
+            HttpResponse::NotFound().body("Tag not found")
+        }
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            log_with_colors("WARN", "PUT 409 /tags/{slug}");
+            HttpResponse::Conflict().body(format!("Tag {} already exists, merge into it instead", name.slug))
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Failed to rename tag: {}", e));
+            HttpResponse::InternalServerError().body("Failed to rename tag")
+        }
+    }
+}
+
+async fn merge_tags(db: &PgPool, source: &TagEntity, target: &TagEntity) -> Result<TagCount, sqlx::Error> {
+    let mut transaction = db.begin().await?;
+
+    // Articles that already carry both tags keep a single link
+    sqlx::query(
+        r#"
+        INSERT INTO article_tags (article_id, tag_id)
+        SELECT article_id, $1 FROM article_tags WHERE tag_id = $2
+        ON CONFLICT DO NOTHING
+        "#
+    )
+        .bind(target.id)
+        .bind(source.id)
+        .execute(&mut *transaction)
+        .await?;
+
+    // Every article of the source now shows the target instead
+    let moved = sqlx::query_scalar::<_, i32>(
+        "UPDATE articles SET version = version + 1 WHERE id IN (SELECT article_id FROM article_tags WHERE tag_id = $1) RETURNING id"
+    )
+        .bind(source.id)
+        .fetch_all(&mut *transaction)
+        .await?;
+
+    // The remaining links of the source go with it
+    sqlx::query("DELETE FROM tags WHERE id = $1")
+        .bind(source.id)
+        .execute(&mut *transaction)
+        .await?;
+
+    transaction.commit().await?;
+
+    // They now share the target with its other articles, which changes their related articles
+    for id in moved {
+        refresh_related_or_log(db, id).await;
+    }
+    load_tag_count(db, target.id).await
+}
+
+// Moves every article of {slug} to the `into` tag and deletes {slug}
+//#[post("/tags/{slug}/merge")]
+pub async fn merge_tag(state: Data<AppState>, claims: ReqData<Claims>, slug: Path<String>, request: Json<TagMergeRequest>) -> HttpResponse {
+    if let Err(response) = require_editor(&state.db, &claims.sub, "POST /tags/{slug}/merge").await {
+        return response;
+    }
+    if slug.as_str() == request.into {
+        log_with_colors("WARN", "POST 400 /tags/{slug}/merge");
+        return HttpResponse::BadRequest().body("Cannot merge a tag into itself");
+    }
+
+    let source = find_tag(&state.db, &slug).await;
+    let target = find_tag(&state.db, &request.into).await;
+    let (source, target) = match (source, target) {
+        (Ok(Some(source)), Ok(Some(target))) => (source, target),
+        (Ok(_), Ok(_)) => {
+            log_with_colors("WARN", "POST 404 /tags/{slug}/merge");
+            return HttpResponse::NotFound().body("Tag not found");
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            return HttpResponse::InternalServerError().body("Failed to load tag");
+        }
+    };
+
+    match merge_tags(&state.db, &source, &target).await {
+        Ok(tag) => {
+            log_with_colors("INFO", "POST 200 /tags/{slug}/merge");
+            HttpResponse::Ok().json(tag)
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Failed to merge tags: {}", e));
+            HttpResponse::InternalServerError().body("Failed to merge tags")
+        }
+    }
+}
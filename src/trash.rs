@@ -0,0 +1,164 @@
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path as FilePath;
+use std::time::Duration;
+use actix_web::web::{Data, Path, ReqData};
+use actix_web::HttpResponse;
+use sqlx::PgPool;
+use crate::entities::{ArticleEntity, Claims, TrashItem};
+use crate::enums::ArticleStatus;
+use crate::review::require_editor;
+use crate::services::{version_mismatch, ArticleWriteError};
+use crate::utils::{article_dir, log_with_colors};
+use crate::AppState;
+
+const DEFAULT_TRASH_RETENTION_DAYS: i32 = 30;
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Days an article stays in the trash before it is purged, TRASH_RETENTION_DAYS overrides it
+fn retention_days() -> i32 {
+    env::var("TRASH_RETENTION_DAYS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS)
+        .max(1)
+}
+
+// Moves an article to the trash, `deleted_by` is the JWT subject and `expected_version` what
+// If-Match asked for. Its slug, tags and files stay until it is purged.
+pub async fn trash_article(db: &PgPool, id: i32, deleted_by: &str, expected_version: Option<i32>) -> Result<(), ArticleWriteError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE articles
+        SET status_before_delete = status, status = $1, deleted_at = now(),
+            deleted_by = (SELECT id FROM users WHERE email = $2), version = version + 1
+        WHERE id = $3 AND status <> $1 AND ($4::INTEGER IS NULL OR version = $4)
+        "#
+    )
+        .bind::<i32>(ArticleStatus::Trashed.into())
+        .bind(deleted_by)
+        .bind(id)
+        .bind(expected_version)
+        .execute(db)
+        .await?;
+
+    match result.rows_affected() {
+        0 => Err(version_mismatch(db, id).await),
+        _ => Ok(()),
+    }
+}
+
+// Puts the article back in the status it was deleted from. A scheduled article
+// that fell due meanwhile is published by the next run of the publisher.
+pub async fn restore_from_trash(db: &PgPool, id: i32) -> Result<Option<ArticleEntity>, sqlx::Error> {
+    sqlx::query_as::<_, ArticleEntity>(
+        r#"
+        UPDATE articles
+        SET status = COALESCE(status_before_delete, $2), status_before_delete = NULL, deleted_at = NULL, deleted_by = NULL,
+            version = version + 1
+        WHERE id = $1 AND status = $3
+        RETURNING *
+        "#
+    )
+        .bind(id)
+        .bind::<i32>(ArticleStatus::Draft.into())
+        .bind::<i32>(ArticleStatus::Trashed.into())
+        .fetch_optional(db)
+        .await
+}
+
+fn remove_file_or_log(path: &FilePath, remove: fn(&FilePath) -> std::io::Result<()>) {
+    match remove(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => log_with_colors("WARN", &format!("Failed to remove {}: {}", path.display(), e)),
+    }
+}
+
+// Removes the articles that were in the trash for longer than the retention, with their files.
+// Revisions, translations, tags and the rest go with the row.
+pub async fn purge_trash(db: &PgPool) -> Result<Vec<i32>, sqlx::Error> {
+    let ids = sqlx::query_scalar::<_, i32>(
+        "DELETE FROM articles WHERE status = $1 AND deleted_at <= now() - make_interval(days => $2) RETURNING id"
+    )
+        .bind::<i32>(ArticleStatus::Trashed.into())
+        .bind(retention_days())
+        .fetch_all(db)
+        .await?;
+
+    let upload_dir = home::home_dir().expect("Failed to get home directory").join("upload");
+    for id in &ids {
+        remove_file_or_log(&article_dir(*id), |path| fs::remove_dir_all(path));
+        remove_file_or_log(&upload_dir.join(format!("{}.md", id)), |path| fs::remove_file(path));
+        remove_file_or_log(&upload_dir.join(format!("{}.jpg", id)), |path| fs::remove_file(path));
+    }
+    Ok(ids)
+}
+
+// Runs for as long as the server does, like the publisher
+pub async fn run_purger(db: PgPool) {
+    loop {
+        match purge_trash(&db).await {
+            Ok(ids) if ids.is_empty() => {}
+            Ok(ids) => log_with_colors("INFO", &format!("Purged trashed articles {:?}", ids)),
+            Err(e) => log_with_colors("ERROR", &format!("Failed to purge the trash: {}", e)),
+        }
+        actix_web::rt::time::sleep(PURGE_INTERVAL).await;
+    }
+}
+
+// The most recently deleted first
+//#[get("/trash")]
+pub async fn fetch_trash(state: Data<AppState>, claims: ReqData<Claims>) -> HttpResponse {
+    if let Err(response) = require_editor(&state.db, &claims.sub, "GET /trash").await {
+        return response;
+    }
+
+    match sqlx::query_as::<_, TrashItem>(
+        r#"
+        SELECT articles.*, users.email AS deleted_by_email,
+            articles.deleted_at + make_interval(days => $2) AS purge_at
+        FROM articles
+        LEFT JOIN users ON users.id = articles.deleted_by
+        WHERE articles.status = $1
+        ORDER BY articles.deleted_at DESC, articles.id DESC
+        "#
+    )
+        .bind::<i32>(ArticleStatus::Trashed.into())
+        .bind(retention_days())
+        .fetch_all(&state.db)
+        .await
+    {
+        Ok(articles) => {
+            log_with_colors("INFO", "GET 200 /trash");
+            HttpResponse::Ok().json(articles)
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Database query failed: {}", e));
+            HttpResponse::InternalServerError().body("Failed to load the trash")
+        }
+    }
+}
+
+//#[post("/trash/{id}/restore")]
+pub async fn restore_article(state: Data<AppState>, claims: ReqData<Claims>, id: Path<i32>) -> HttpResponse {
+    if let Err(response) = require_editor(&state.db, &claims.sub, "POST /trash/{id}/restore").await {
+        return response;
+    }
+
+    match restore_from_trash(&state.db, id.into_inner()).await {
+        Ok(Some(article)) => {
+            log_with_colors("INFO", "POST 200 /trash/{id}/restore");
+            HttpResponse::Ok().json(article)
+        }
+        Ok(None) => {
+            log_with_colors("WARN", "POST 404 /trash/{id}/restore");
+            HttpResponse::NotFound().body("Article not in the trash")
+        }
+        Err(e) => {
+            log_with_colors("ERROR", &format!("Failed to restore article: {}", e));
+            HttpResponse::InternalServerError().body("Failed to restore article")
+        }
+    }
+}
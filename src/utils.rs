@@ -1,13 +1,20 @@
 use std::fs::File;
 use std::io::{self, Read};
+use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
+use actix_web::http::header::{CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use actix_web::{HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
 use colored::*;
 use jsonwebtoken::{encode, decode, Header, Algorithm, Validation, EncodingKey, DecodingKey, errors::Error as JwtError};
 use std::env;
 use bcrypt::{hash, DEFAULT_COST};
+use pulldown_cmark::{html, Event, Options, Parser, TagEnd};
+use sha2::{Digest, Sha256};
 use sqlx::PgPool;
 use crate::entities;
-use entities::Claims;
+use crate::enums::UserRole;
+use entities::{Claims, PreviewClaims};
 
 // Function to simulate Spring Boot-style logging with timestamp and colors
 pub fn log_with_colors(level: &str, message: &str) {
@@ -31,6 +38,41 @@ pub fn log_with_colors(level: &str, message: &str) {
 
 // FILE UTILS
 
+// Path of one of the files kept in an article's folder
+pub fn article_file_path(id: i32, filename: &str) -> PathBuf {
+    article_dir(id).join(filename)
+}
+
+// Directory of an article's markdown, photo and translations
+pub fn article_dir(id: i32) -> PathBuf {
+    let home_dir = home::home_dir().expect("Failed to get home directory");
+    home_dir.join(format!("hephaestus-blog/articles/{}", id))
+}
+
+// Location fetch_article reads an article's markdown from
+pub fn article_md_path(id: i32) -> PathBuf {
+    article_file_path(id, &format!("{}.md", id))
+}
+
+// Location fetch_article reads an article's photo from
+pub fn article_photo_path(id: i32) -> PathBuf {
+    article_file_path(id, &format!("{}.jpg", id))
+}
+
+// Markdown of an article as stored, articles created before create wrote to
+// the article's folder still have it in ~/upload
+pub fn read_article_markdown(id: i32) -> io::Result<String> {
+    let md_file_path = article_md_path(id);
+    match read_file_contents(md_file_path.to_str().expect("Failed to convert markdown file path to string")) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let home_dir = home::home_dir().expect("Failed to get home directory");
+            let legacy_path = home_dir.join(format!("upload/{}.md", id));
+            read_file_contents(legacy_path.to_str().expect("Failed to convert markdown file path to string"))
+        }
+        result => result,
+    }
+}
+
 pub fn read_file_contents(file_path: &str) -> io::Result<String> {
     let mut file = File::open(file_path)?;
     let mut contents = String::new();
@@ -38,6 +80,31 @@ pub fn read_file_contents(file_path: &str) -> io::Result<String> {
     Ok(contents)
 }
 
+// Render article markdown to HTML for the site and feeds
+pub fn markdown_to_html(markdown: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, Parser::new_ext(markdown, options));
+    html_output
+}
+
+// Plain text of a markdown document, markup dropped and blocks separated by newlines
+pub fn markdown_to_text(markdown: &str) -> String {
+    let mut text = String::new();
+    for event in Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH) {
+        match event {
+            Event::Text(value) | Event::Code(value) => text.push_str(&value),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item | TagEnd::CodeBlock | TagEnd::TableCell) => text.push('\n'),
+            _ => {}
+        }
+    }
+    text
+}
+
 // Function to read photo as base64 string
 pub fn read_photo_as_base64(file_path: &str) -> io::Result<String> {
     let mut file = File::open(file_path)?;
@@ -80,6 +147,183 @@ pub fn validate_jwt(token: &str) -> Result<Claims, JwtError> {
         .map(|data| data.claims)
 }
 
+// Preview tokens are signed with the same secret and expire with their link
+pub fn generate_preview_token(preview_id: i32, article_id: i32, expires_at: DateTime<Utc>) -> Result<String, JwtError> {
+    let claims = PreviewClaims {
+        preview: preview_id,
+        article: article_id,
+        exp: expires_at.timestamp().max(0) as usize,
+    };
+
+    let secret_key = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret_key.as_ref()),
+    )
+}
+
+pub fn validate_preview_token(token: &str) -> Result<PreviewClaims, JwtError> {
+    let secret_key = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
+
+    decode::<PreviewClaims>(
+        token,
+        &DecodingKey::from_secret(secret_key.as_ref()),
+        &Validation::new(Algorithm::HS256),
+    )
+        .map(|data| data.claims)
+}
+
+// SITE UTILS
+
+// Public address of the service, used for absolute links in feeds
+pub fn site_url() -> String {
+    env::var("SITE_URL")
+        .unwrap_or_else(|_| "http://127.0.0.1:8080".to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+pub fn site_title() -> String {
+    env::var("SITE_TITLE").unwrap_or_else(|_| "Hephaestus Blog".to_string())
+}
+
+// Absolute link to an article, the HTML page when the site is enabled and the JSON otherwise
+pub fn article_url(slug: &str) -> String {
+    if env::var("SITE_THEME_DIR").is_ok() {
+        format!("{}/posts/{}", site_url(), slug)
+    } else {
+        format!("{}/articles/{}", site_url(), slug)
+    }
+}
+
+// Greek to Latin after ELOT 743, the Greek standard for transliterating names.
+// Expects lowercase text, everything that is not Greek passes through.
+pub fn transliterate_greek(text: &str) -> String {
+    let mut latin = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let next = chars.peek().copied();
+        let digraph = match (c, next) {
+            ('ο', Some('υ' | 'ύ')) => Some("ou"),
+            ('α' | 'ά', Some('υ' | 'ύ')) => Some("av"),
+            ('ε' | 'έ', Some('υ' | 'ύ')) => Some("ev"),
+            ('η' | 'ή', Some('υ' | 'ύ')) => Some("iv"),
+            ('γ', Some('γ')) => Some("ng"),
+            ('γ', Some('ξ')) => Some("nx"),
+            ('γ', Some('χ')) => Some("nch"),
+            _ => None,
+        };
+        if let Some(digraph) = digraph {
+            latin.push_str(digraph);
+            chars.next();
+            continue;
+        }
+
+        let single = match c {
+            'α' | 'ά' => "a",
+            'β' => "v",
+            'γ' => "g",
+            'δ' => "d",
+            'ε' | 'έ' => "e",
+            'ζ' => "z",
+            'η' | 'ή' => "i",
+            'θ' => "th",
+            'ι' | 'ί' | 'ϊ' | 'ΐ' => "i",
+            'κ' => "k",
+            'λ' => "l",
+            'μ' => "m",
+            'ν' => "n",
+            'ξ' => "x",
+            'ο' | 'ό' => "o",
+            'π' => "p",
+            'ρ' => "r",
+            'σ' | 'ς' => "s",
+            'τ' => "t",
+            'υ' | 'ύ' | 'ϋ' | 'ΰ' => "y",
+            'φ' => "f",
+            'χ' => "ch",
+            'ψ' => "ps",
+            'ω' | 'ώ' => "o",
+            _ => {
+                latin.push(c);
+                continue;
+            }
+        };
+        latin.push_str(single);
+    }
+
+    latin
+}
+
+// URL-safe form of a name: lowercase ASCII letters and digits joined by single hyphens.
+// Greek is transliterated, other scripts go through deunicode.
+pub fn slugify(name: &str) -> String {
+    deunicode::deunicode(&transliterate_greek(&name.to_lowercase()))
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// HTTP UTILS
+
+// ETag of an article, the version it is at
+pub fn article_etag(version: i32) -> String {
+    format!("\"{}\"", version)
+}
+
+// Version of an article_etag, None for anything else. A weak ETag never matches If-Match.
+pub fn parse_article_etag(tag: &str) -> Option<i32> {
+    tag.strip_prefix('"')?.strip_suffix('"')?.parse().ok()
+}
+
+const CACHE_MAX_AGE: u32 = 300; // seconds clients and proxies may cache feeds and sitemaps
+
+// Respond with caching headers, or 304 when the client's copy is still current
+pub fn cached_response(req: &HttpRequest, content_type: &str, body: String, last_modified: Option<DateTime<Utc>>) -> HttpResponse {
+    let etag = format!("\"{:x}\"", Sha256::digest(body.as_bytes()));
+    let last_modified_header = last_modified.map(|date| date.format("%a, %d %b %Y %H:%M:%S GMT").to_string());
+
+    let etag_matches = req.headers()
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*"));
+
+    // If-Modified-Since is only considered when the client did not send an ETag
+    let not_modified = etag_matches.unwrap_or_else(|| {
+        let since = req.headers()
+            .get(IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+        match (since, last_modified) {
+            (Some(since), Some(last_modified)) => last_modified.timestamp() <= since.timestamp(),
+            _ => false,
+        }
+    });
+
+    let mut response = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response
+        .insert_header((ETAG, etag))
+        .insert_header((CACHE_CONTROL, format!("public, max-age={}", CACHE_MAX_AGE)));
+    if let Some(last_modified_header) = last_modified_header {
+        response.insert_header((LAST_MODIFIED, last_modified_header));
+    }
+
+    if not_modified {
+        response.finish()
+    } else {
+        response.content_type(content_type).body(body)
+    }
+}
+
 // GENERAL UTILS
 pub async fn create_default_user_if_not_exists(db_pool: &PgPool) {
     // Load email and password from environment variables
@@ -99,9 +343,11 @@ pub async fn create_default_user_if_not_exists(db_pool: &PgPool) {
         let hashed_password = hash(&default_password, DEFAULT_COST).unwrap();
 
         // Insert the default user into the database
-        sqlx::query("INSERT INTO users (email, password_hash) VALUES ($1, $2)")
+        // The first user runs the site, so it is an editor
+        sqlx::query("INSERT INTO users (email, password_hash, role) VALUES ($1, $2, $3)")
             .bind(&default_email)
             .bind(&hashed_password)
+            .bind::<i32>(UserRole::Editor.into())
             .execute(db_pool)
             .await
             .unwrap();